@@ -0,0 +1,271 @@
+use crate::bvh::Aabb;
+use crate::math::float::Float;
+use crate::math::{clamp, partial_max};
+use crate::matrix::{transform_aabb, Matrix4};
+use crate::quaternion::Quaternion;
+use crate::vec3::Vec3;
+
+/// Number of steps between two keyframes used to bound the motion of an AABB.
+const BOUNDING_BOX_STEPS: usize = 16;
+
+/// The transformation at a point in time. Points are scaled first, then rotated and finally
+/// translated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub time: Float,
+    pub translation: Vec3,
+    pub rotation: Quaternion,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(time: Float, translation: Vec3, rotation: Quaternion, scale: Vec3) -> Keyframe {
+        Keyframe {
+            time,
+            translation,
+            rotation: rotation.normalized(),
+            scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        Matrix4::translation(self.translation)
+            * self.rotation.to_matrix()
+            * Matrix4::scaling(self.scale)
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        self.rotation.rotate(&(*p * self.scale)) + self.translation
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        self.rotation.rotate(&(*v * self.scale))
+    }
+
+    /// Transforms a surface normal with the inverse transpose. The result is normalized.
+    pub fn transform_normal(&self, n: &Vec3) -> Vec3 {
+        self.rotation.rotate(&(*n / self.scale)).unit_vector()
+    }
+
+    pub fn inverse_transform_point(&self, p: &Vec3) -> Vec3 {
+        self.rotation.conjugate().rotate(&(*p - self.translation)) / self.scale
+    }
+
+    pub fn inverse_transform_vector(&self, v: &Vec3) -> Vec3 {
+        self.rotation.conjugate().rotate(v) / self.scale
+    }
+
+    fn interpolate(&self, other: &Keyframe, time: Float) -> Keyframe {
+        let t = (time - self.time) / (other.time - self.time);
+        Keyframe {
+            time,
+            translation: (1. - t) * self.translation + t * other.translation,
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: (1. - t) * self.scale + t * other.scale,
+        }
+    }
+}
+
+/// A transformation which is interpolated between keyframes (translation and scale linearly,
+/// rotation with slerp). Before the first and after the last keyframe it stays constant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedTransform {
+    keyframes: Vec<Keyframe>,
+}
+
+impl AnimatedTransform {
+    /// Panics if `keyframes` is empty.
+    pub fn new(mut keyframes: Vec<Keyframe>) -> AnimatedTransform {
+        assert!(!keyframes.is_empty(), "at least one keyframe is required");
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        AnimatedTransform { keyframes }
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn at_time(&self, time: Float) -> Keyframe {
+        let first = &self.keyframes[0];
+        let last = &self.keyframes[self.keyframes.len() - 1];
+        if time <= first.time {
+            return *first;
+        }
+        if time >= last.time {
+            return *last;
+        }
+        let i = self.keyframes.iter().position(|k| k.time > time).unwrap();
+        self.keyframes[i - 1].interpolate(&self.keyframes[i], time)
+    }
+
+    /// An AABB containing `aabb` transformed at any time in `[time_start, time_end]`.
+    pub fn bounding_box(&self, aabb: &Aabb, time_start: Float, time_end: Float) -> Aabb {
+        let mut times = vec![time_start];
+        times.extend(
+            self.keyframes
+                .iter()
+                .map(|k| k.time)
+                .filter(|&t| t > time_start && t < time_end),
+        );
+        times.push(time_end);
+
+        let corners = aabb.corners();
+        let mut previous = self.at_time(time_start);
+        let mut result = transform_aabb(&previous.matrix(), aabb);
+        for interval in times.windows(2) {
+            for step in 1..=BOUNDING_BOX_STEPS {
+                let time = interval[0]
+                    + (interval[1] - interval[0]) * step as Float / BOUNDING_BOX_STEPS as Float;
+                let current = self.at_time(time);
+                let bb = transform_aabb(&current.matrix(), aabb);
+
+                // Between two steps the corners move on arcs which can leave the AABB of both
+                // steps. Pad the AABB by a bound on the distance of such an arc to its chord.
+                let half_angle = previous.rotation.angle_to(&current.rotation) / 2.;
+                let mut padding: Float = 0.;
+                for c in corners.iter() {
+                    let radius = partial_max(
+                        (*c * previous.scale).length(),
+                        (*c * current.scale).length(),
+                    );
+                    let scale_change = (*c * (current.scale - previous.scale)).length();
+                    padding = partial_max(
+                        padding,
+                        radius * (1. - half_angle.cos()) + 0.5 * scale_change * half_angle.sin(),
+                    );
+                }
+                let padding = Vec3::new(padding, padding, padding);
+
+                result = result.union(&Aabb::new(bb.min - padding, bb.max + padding));
+                previous = current;
+            }
+        }
+        result
+    }
+}
+
+/// The path of a point over time.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MotionPath {
+    Static(Vec3),
+    /// `origin + time * velocity` for any time.
+    Linear {
+        origin: Vec3,
+        velocity: Vec3,
+    },
+    /// Linear interpolation between `(time, position)` keys sorted by time. The position stays
+    /// constant before the first and after the last key.
+    PiecewiseLinear(Vec<(Float, Vec3)>),
+    /// A cubic Bezier curve which is traversed from `time_start` to `time_end`. The position stays
+    /// constant outside of that interval.
+    Bezier {
+        control_points: [Vec3; 4],
+        time_start: Float,
+        time_end: Float,
+    },
+}
+
+impl MotionPath {
+    pub fn linear(origin: Vec3, velocity: Vec3) -> MotionPath {
+        MotionPath::Linear { origin, velocity }
+    }
+
+    /// Panics if `keys` is empty.
+    pub fn piecewise_linear(mut keys: Vec<(Float, Vec3)>) -> MotionPath {
+        assert!(!keys.is_empty(), "at least one key is required");
+        keys.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        MotionPath::PiecewiseLinear(keys)
+    }
+
+    pub fn bezier(control_points: [Vec3; 4], time_start: Float, time_end: Float) -> MotionPath {
+        assert!(time_start < time_end);
+        MotionPath::Bezier {
+            control_points,
+            time_start,
+            time_end,
+        }
+    }
+
+    pub fn is_static(&self) -> bool {
+        matches!(self, MotionPath::Static(_))
+    }
+
+    pub fn position_at(&self, time: Float) -> Vec3 {
+        match self {
+            MotionPath::Static(p) => *p,
+            MotionPath::Linear { origin, velocity } => *origin + time * *velocity,
+            MotionPath::PiecewiseLinear(keys) => {
+                let (first_time, first) = keys[0];
+                let (last_time, last) = keys[keys.len() - 1];
+                if time <= first_time {
+                    return first;
+                }
+                if time >= last_time {
+                    return last;
+                }
+                let i = keys.iter().position(|k| k.0 > time).unwrap();
+                let (t0, p0) = keys[i - 1];
+                let (t1, p1) = keys[i];
+                let t = (time - t0) / (t1 - t0);
+                (1. - t) * p0 + t * p1
+            }
+            MotionPath::Bezier {
+                control_points,
+                time_start,
+                time_end,
+            } => {
+                let t = clamp((time - time_start) / (time_end - time_start), 0., 1.);
+                split_bezier(control_points, t).0[3]
+            }
+        }
+    }
+
+    /// An AABB containing every position in `[time_start, time_end]`.
+    pub fn bounding_box(&self, time_start: Float, time_end: Float) -> Aabb {
+        let point = |p: Vec3| Aabb::new(p, p);
+        match self {
+            MotionPath::Static(p) => point(*p),
+            MotionPath::Linear { .. } => {
+                point(self.position_at(time_start)).union(&point(self.position_at(time_end)))
+            }
+            MotionPath::PiecewiseLinear(keys) => keys
+                .iter()
+                .filter(|k| k.0 > time_start && k.0 < time_end)
+                .fold(
+                    point(self.position_at(time_start)).union(&point(self.position_at(time_end))),
+                    |aabb, k| aabb.union(&point(k.1)),
+                ),
+            MotionPath::Bezier {
+                control_points,
+                time_start: path_start,
+                time_end: path_end,
+            } => {
+                // A Bezier curve is contained in the convex hull of its control points so we
+                // bound the control points of the part of the curve inside the interval.
+                let t0 = clamp((time_start - path_start) / (path_end - path_start), 0., 1.);
+                let t1 = clamp((time_end - path_start) / (path_end - path_start), 0., 1.);
+                let tail = split_bezier(control_points, t0).1;
+                let segment = if t0 < 1. {
+                    split_bezier(&tail, (t1 - t0) / (1. - t0)).0
+                } else {
+                    tail
+                };
+                segment
+                    .iter()
+                    .fold(Aabb::empty(), |aabb, p| aabb.union(&point(*p)))
+            }
+        }
+    }
+}
+
+/// Splits a cubic Bezier curve at `t` using de Casteljau's algorithm.
+pub(crate) fn split_bezier(cp: &[Vec3; 4], t: Float) -> ([Vec3; 4], [Vec3; 4]) {
+    let lerp = |a: Vec3, b: Vec3| (1. - t) * a + t * b;
+    let p01 = lerp(cp[0], cp[1]);
+    let p12 = lerp(cp[1], cp[2]);
+    let p23 = lerp(cp[2], cp[3]);
+    let p012 = lerp(p01, p12);
+    let p123 = lerp(p12, p23);
+    let p0123 = lerp(p012, p123);
+    ([cp[0], p01, p012, p0123], [p0123, p123, p23, cp[3]])
+}
@@ -6,6 +6,7 @@ use crate::hit::{Hit, HitRecord};
 use crate::math::float::{self, Float};
 use crate::math::{partial_max, partial_min};
 use crate::ray::Ray;
+use crate::stats;
 use crate::vec3::Vec3;
 use crate::Rng;
 
@@ -38,20 +39,72 @@ impl fmt::Display for BvhError {
 
 impl Error for BvhError {}
 
+/// A bounding volume hierarchy. Objects without a bounding box (e.g. `Plane`) can't be sorted
+/// into the tree so they are kept in a separate list which is always tested.
 #[derive(Debug)]
 pub struct Bvh<R: Rng> {
+    root: Option<Box<dyn Hit<R>>>,
+    unbounded: Vec<Box<dyn Hit<R>>>,
+}
+
+impl<R: Rng> Bvh<R> {
+    pub fn new(
+        hit_list: Vec<Box<dyn Hit<R>>>,
+        time_start: Float,
+        time_end: Float,
+        rng: &mut R,
+    ) -> Result<Bvh<R>, BvhError> {
+        let (mut bounded, unbounded): (Vec<_>, Vec<_>) = hit_list
+            .into_iter()
+            .partition(|hit| hit.bounding_box(time_start, time_end).is_some());
+
+        let root = match (bounded.len(), unbounded.len()) {
+            (n, 0) if n < 2 => return Err(BvhError::TooFewElements(n as u8)),
+            (0, _) => None,
+            (1, _) => bounded.pop(),
+            _ => {
+                Some(Box::new(BvhNode::new(bounded, time_start, time_end, rng)?) as Box<dyn Hit<R>>)
+            }
+        };
+        Ok(Bvh { root, unbounded })
+    }
+}
+
+impl<R: Rng> Hit<R> for Bvh<R> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, rng: &mut R) -> Option<HitRecord<'_>> {
+        let closest = self
+            .root
+            .as_ref()
+            .and_then(|root| root.hit(ray, t_min, t_max, rng));
+        let closest_t = closest.as_ref().map_or(t_max, |hit_record| hit_record.t);
+        self.unbounded.hit(ray, t_min, closest_t, rng).or(closest)
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            self.root
+                .as_ref()
+                .and_then(|root| root.bounding_box(time_start, time_end))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BvhNode<R: Rng> {
     left: Box<dyn Hit<R>>,
     right: Box<dyn Hit<R>>,
     aabb: Aabb,
 }
 
-impl<R: Rng> Bvh<R> {
-    pub fn new(
+impl<R: Rng> BvhNode<R> {
+    fn new(
         mut hit_list: Vec<Box<dyn Hit<R>>>,
         time_start: Float,
         time_end: Float,
         rng: &mut R,
-    ) -> Result<Bvh<R>, BvhError> {
+    ) -> Result<BvhNode<R>, BvhError> {
         let axis = rng.gen_range(0, 3);
         let mut error = None;
         hit_list.sort_unstable_by(|a, b| {
@@ -99,22 +152,22 @@ impl<R: Rng> Bvh<R> {
             3 => {
                 let right = hit_list.pop().unwrap();
                 let left =
-                    Box::new(Bvh::new(hit_list, time_start, time_end, rng)?) as Box<dyn Hit<R>>;
+                    Box::new(BvhNode::new(hit_list, time_start, time_end, rng)?) as Box<dyn Hit<R>>;
                 (left, right)
             }
             _ => {
                 let hit_list_len = hit_list.len();
                 let right_half = hit_list.split_off(hit_list_len / 2);
                 let left =
-                    Box::new(Bvh::new(hit_list, time_start, time_end, rng)?) as Box<dyn Hit<R>>;
-                let right =
-                    Box::new(Bvh::new(right_half, time_start, time_end, rng)?) as Box<dyn Hit<R>>;
+                    Box::new(BvhNode::new(hit_list, time_start, time_end, rng)?) as Box<dyn Hit<R>>;
+                let right = Box::new(BvhNode::new(right_half, time_start, time_end, rng)?)
+                    as Box<dyn Hit<R>>;
                 (left, right)
             }
         };
 
         // The `unwrap()`s are safe as we'll only reach this part if all AABBs are valid.
-        Ok(Bvh {
+        Ok(BvhNode {
             aabb: left
                 .bounding_box(time_start, time_end)
                 .unwrap()
@@ -125,8 +178,9 @@ impl<R: Rng> Bvh<R> {
     }
 }
 
-impl<R: Rng> Hit<R> for Bvh<R> {
+impl<R: Rng> Hit<R> for BvhNode<R> {
     fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, rng: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_aabb_test();
         if self.aabb.hit(ray, t_min, t_max) {
             match (
                 self.left.hit(ray, t_min, t_max, rng),
@@ -192,6 +246,52 @@ impl Aabb {
         true
     }
 
+    /// Like `hit` but returns the parameter interval of the ray inside of the AABB.
+    pub fn hit_interval(
+        &self,
+        ray: &Ray,
+        mut t_min: Float,
+        mut t_max: Float,
+    ) -> Option<(Float, Float)> {
+        for a in 0..3 {
+            let inv_d = 1.0 / ray.direction()[a];
+            let mut t0 = (self.min[a] - ray.origin()[a]) * inv_d;
+            let mut t1 = (self.max[a] - ray.origin()[a]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = partial_max(t0, t_min);
+            t_max = partial_min(t1, t_max);
+            if t_max <= t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let mut corners = [Vec3::zero(); 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let x = if i & 1 == 0 {
+                self.min.x()
+            } else {
+                self.max.x()
+            };
+            let y = if i & 2 == 0 {
+                self.min.y()
+            } else {
+                self.max.y()
+            };
+            let z = if i & 4 == 0 {
+                self.min.z()
+            } else {
+                self.max.z()
+            };
+            *corner = Vec3::new(x, y, z);
+        }
+        corners
+    }
+
     pub fn union(&self, other: &Aabb) -> Aabb {
         Aabb::new(
             Vec3::new(
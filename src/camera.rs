@@ -2,6 +2,7 @@ use crate::math::float::consts::PI;
 use crate::math::float::Float;
 
 use crate::ray::Ray;
+use crate::spectrum;
 use crate::vec3::Vec3;
 use crate::Rng;
 
@@ -62,14 +63,20 @@ impl Camera {
         }
     }
 
+    /// With the `spectral` feature every ray carries a uniformly sampled wavelength.
     pub fn get_ray<T: Rng>(&self, s: Float, t: Float, rng: &mut T) -> Ray {
         let rd = self.lens_radius * random_in_unit_disk(rng);
         let offset = self.u * rd.x() + self.v * rd.y();
-        Ray::new(
+        let ray = Ray::new(
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
             self.time + rng.gen::<Float>() * self.parameters.exposure_time,
-        )
+        );
+        if cfg!(feature = "spectral") {
+            ray.with_wavelength(Some(spectrum::sample_wavelength(rng)))
+        } else {
+            ray
+        }
     }
 }
 
@@ -0,0 +1,361 @@
+use crate::hit::HitRecord;
+use crate::material::MaterialTrait;
+use crate::math::clamp;
+use crate::math::float::consts::{LN_2, PI};
+use crate::math::float::Float;
+use crate::microfacet::fresnel_dielectric;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// Number of explicitly modeled scattering lobes (R, TT and TRT). All higher-order paths are
+/// lumped into one more lobe.
+const P_MAX: usize = 3;
+const SQRT_PI_OVER_8: Float = 0.626_657_07;
+const ETA: Float = 1.55;
+/// Tilt of the cuticle scales in degrees.
+const ALPHA: Float = 2.;
+
+const EUMELANIN_SIGMA_A: (Float, Float, Float) = (0.419, 0.697, 1.37);
+const PHEOMELANIN_SIGMA_A: (Float, Float, Float) = (0.187, 0.4, 1.05);
+
+/// The hair scattering model of Chiang et al. ("A Practical and Controllable Hair and Fur Model
+/// for Production Path Tracing", 2016) as implemented in pbrt.
+///
+/// Meant for `Curve`s: the tangent is taken from `dpdu` and `v` gives the offset across the
+/// fiber. `beta_m` and `beta_n` are the longitudinal and azimuthal roughness in `[0, 1]`.
+#[derive(Debug, Clone)]
+pub struct Hair {
+    sigma_a: Vec3,
+    v: [Float; P_MAX + 1],
+    s: Float,
+    sin_2k_alpha: [Float; 3],
+    cos_2k_alpha: [Float; 3],
+}
+
+impl Hair {
+    /// `sigma_a` is the absorption coefficient inside the fiber (relative to its diameter).
+    pub fn new(sigma_a: Vec3, beta_m: Float, beta_n: Float) -> Hair {
+        let v0 = 0.726 * beta_m + 0.812 * beta_m * beta_m + 3.7 * beta_m.powi(20);
+        let v0 = v0 * v0;
+        let s =
+            SQRT_PI_OVER_8 * (0.265 * beta_n + 1.194 * beta_n * beta_n + 5.372 * beta_n.powi(22));
+
+        let mut sin_2k_alpha = [ALPHA.to_radians().sin(), 0., 0.];
+        let mut cos_2k_alpha = [(1. - sin_2k_alpha[0] * sin_2k_alpha[0]).sqrt(), 0., 0.];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2. * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1] * cos_2k_alpha[i - 1]
+                - sin_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+        }
+
+        Hair {
+            sigma_a,
+            v: [v0, 0.25 * v0, 4. * v0, 4. * v0],
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    /// Hair colored by the concentrations of the two pigments found in human hair (roughly 0 to
+    /// 8 for eumelanin, from blond to black).
+    pub fn from_melanin(
+        eumelanin: Float,
+        pheomelanin: Float,
+        beta_m: Float,
+        beta_n: Float,
+    ) -> Hair {
+        let (e, p) = (EUMELANIN_SIGMA_A, PHEOMELANIN_SIGMA_A);
+        let sigma_a = eumelanin * Vec3::new(e.0, e.1, e.2) + pheomelanin * Vec3::new(p.0, p.1, p.2);
+        Hair::new(sigma_a, beta_m, beta_n)
+    }
+
+    /// Hair whose multiply scattered color is roughly `color`.
+    pub fn from_color(color: Vec3, beta_m: Float, beta_n: Float) -> Hair {
+        let denominator = 5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+            + 5.574 * beta_n.powi(4)
+            + 0.245 * beta_n.powi(5);
+        let sigma_a = |c: Float| {
+            let x = c.max(1e-4).ln() / denominator;
+            x * x
+        };
+        Hair::new(
+            Vec3::new(sigma_a(color.r()), sigma_a(color.g()), sigma_a(color.b())),
+            beta_m,
+            beta_n,
+        )
+    }
+
+    /// The angles of the outgoing direction rotated by the tilt of the scales for lobe `p`.
+    fn tilted(&self, p: usize, sin_theta_o: Float, cos_theta_o: Float) -> (Float, Float) {
+        let (sin, cos) = match p {
+            0 => (
+                sin_theta_o * self.cos_2k_alpha[1] - cos_theta_o * self.sin_2k_alpha[1],
+                cos_theta_o * self.cos_2k_alpha[1] + sin_theta_o * self.sin_2k_alpha[1],
+            ),
+            1 => (
+                sin_theta_o * self.cos_2k_alpha[0] + cos_theta_o * self.sin_2k_alpha[0],
+                cos_theta_o * self.cos_2k_alpha[0] - sin_theta_o * self.sin_2k_alpha[0],
+            ),
+            2 => (
+                sin_theta_o * self.cos_2k_alpha[2] + cos_theta_o * self.sin_2k_alpha[2],
+                cos_theta_o * self.cos_2k_alpha[2] - sin_theta_o * self.sin_2k_alpha[2],
+            ),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+        (sin, cos.abs())
+    }
+
+    /// The attenuation of each lobe and the azimuthal angle of the refracted ray.
+    fn attenuation(&self, sin_theta_o: Float, h: Float) -> ([Vec3; P_MAX + 1], Float) {
+        let cos_theta_o = safe_sqrt(1. - sin_theta_o * sin_theta_o);
+        let sin_theta_t = sin_theta_o / ETA;
+        let cos_theta_t = safe_sqrt(1. - sin_theta_t * sin_theta_t);
+        let eta_p = (ETA * ETA - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o.max(1e-5);
+        let sin_gamma_t = clamp(h / eta_p, -1., 1.);
+        let cos_gamma_t = safe_sqrt(1. - sin_gamma_t * sin_gamma_t);
+
+        // Transmittance of a single path through the fiber
+        let d = 2. * cos_gamma_t / cos_theta_t.max(1e-5);
+        let transmittance = Vec3::new(
+            (-self.sigma_a.r() * d).exp(),
+            (-self.sigma_a.g() * d).exp(),
+            (-self.sigma_a.b() * d).exp(),
+        );
+
+        let cos_gamma_o = safe_sqrt(1. - h * h);
+        let f = fresnel_dielectric(cos_theta_o * cos_gamma_o, ETA);
+        let mut ap = [Vec3::zero(); P_MAX + 1];
+        ap[0] = Vec3::new(f, f, f);
+        ap[1] = (1. - f) * (1. - f) * transmittance;
+        for p in 2..P_MAX {
+            ap[p] = ap[p - 1] * transmittance * f;
+        }
+        let one = Vec3::new(1., 1., 1.);
+        ap[P_MAX] = ap[P_MAX - 1] * f * transmittance / (one - transmittance * f);
+        (ap, sin_gamma_t.asin())
+    }
+
+    /// The BSDF times the cosine to the normal for the local directions `wo` and `wi` (x along
+    /// the fiber, z is the normal).
+    fn evaluate(&self, wo: &Vec3, wi: &Vec3, h: Float) -> Vec3 {
+        let (sin_theta_o, cos_theta_o, phi_o) = angles(wo);
+        let (sin_theta_i, cos_theta_i, phi_i) = angles(wi);
+        let gamma_o = clamp(h, -1., 1.).asin();
+        let (ap, gamma_t) = self.attenuation(sin_theta_o, h);
+        let phi = phi_i - phi_o;
+
+        let mut f = Vec3::zero();
+        for (p, ap) in ap.iter().enumerate().take(P_MAX) {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            let mp = longitudinal(
+                cos_theta_i,
+                cos_theta_op,
+                sin_theta_i,
+                sin_theta_op,
+                self.v[p],
+            );
+            f += mp * azimuthal(phi, p, self.s, gamma_o, gamma_t) * *ap;
+        }
+        let mp = longitudinal(
+            cos_theta_i,
+            cos_theta_o,
+            sin_theta_i,
+            sin_theta_o,
+            self.v[P_MAX],
+        );
+        f += mp / (2. * PI) * ap[P_MAX];
+        f
+    }
+
+    /// The probability of sampling each lobe, proportional to its attenuation.
+    fn lobe_pdfs(&self, sin_theta_o: Float, h: Float) -> [Float; P_MAX + 1] {
+        let (ap, _) = self.attenuation(sin_theta_o, h);
+        let luminance = |c: &Vec3| 0.212_671 * c.r() + 0.715_160 * c.g() + 0.072_169 * c.b();
+        let sum: Float = ap.iter().map(luminance).sum();
+        let mut pdfs = [0.; P_MAX + 1];
+        for (pdf, ap) in pdfs.iter_mut().zip(ap.iter()) {
+            *pdf = luminance(ap) / sum;
+        }
+        pdfs
+    }
+
+    fn pdf(&self, wo: &Vec3, wi: &Vec3, h: Float) -> Float {
+        let (sin_theta_o, cos_theta_o, phi_o) = angles(wo);
+        let (sin_theta_i, cos_theta_i, phi_i) = angles(wi);
+        let gamma_o = clamp(h, -1., 1.).asin();
+        let (_, gamma_t) = self.attenuation(sin_theta_o, h);
+        let lobe_pdfs = self.lobe_pdfs(sin_theta_o, h);
+        let phi = phi_i - phi_o;
+
+        let mut pdf = 0.;
+        for (p, lobe_pdf) in lobe_pdfs.iter().enumerate().take(P_MAX) {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            let mp = longitudinal(
+                cos_theta_i,
+                cos_theta_op,
+                sin_theta_i,
+                sin_theta_op,
+                self.v[p],
+            );
+            pdf += mp * lobe_pdf * azimuthal(phi, p, self.s, gamma_o, gamma_t);
+        }
+        let mp = longitudinal(
+            cos_theta_i,
+            cos_theta_o,
+            sin_theta_i,
+            sin_theta_o,
+            self.v[P_MAX],
+        );
+        pdf + mp * lobe_pdfs[P_MAX] / (2. * PI)
+    }
+
+    fn sample<R: Rng>(&self, wo: &Vec3, h: Float, rng: &mut R) -> Vec3 {
+        let (sin_theta_o, cos_theta_o, phi_o) = angles(wo);
+        let gamma_o = clamp(h, -1., 1.).asin();
+        let (_, gamma_t) = self.attenuation(sin_theta_o, h);
+
+        let mut u = rng.gen::<Float>();
+        let lobe_pdfs = self.lobe_pdfs(sin_theta_o, h);
+        let mut p = 0;
+        while p < P_MAX && u >= lobe_pdfs[p] {
+            u -= lobe_pdfs[p];
+            p += 1;
+        }
+
+        // Longitudinal angle
+        let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+        let u = rng.gen::<Float>().max(1e-5);
+        let v = self.v[p];
+        let cos_theta = 1. + v * (u + (1. - u) * (-2. / v).exp()).ln();
+        let sin_theta = safe_sqrt(1. - cos_theta * cos_theta);
+        let cos_phi = (2. * PI * rng.gen::<Float>()).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1. - sin_theta_i * sin_theta_i);
+
+        // Azimuthal angle
+        let dphi = if p < P_MAX {
+            phi(p, gamma_o, gamma_t) + sample_trimmed_logistic(rng.gen(), self.s, -PI, PI)
+        } else {
+            2. * PI * rng.gen::<Float>()
+        };
+        let phi_i = phi_o + dphi;
+        Vec3::new(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        )
+    }
+}
+
+impl MaterialTrait for Hair {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        let frame = Onb::from_w_and_tangent(&hit_record.normal, &hit_record.dpdu);
+        let wo = frame.to_local(&-ray.direction().unit_vector());
+        let h = clamp(2. * hit_record.v - 1., -1., 1.);
+
+        let wi = self.sample(&wo, h, rng);
+        let pdf = self.pdf(&wo, &wi, h);
+        if pdf <= 0. || !pdf.is_finite() {
+            return None;
+        }
+        let attenuation = self.evaluate(&wo, &wi, h) / pdf;
+        Some((ray.spawn(hit_record.p, frame.to_world(&wi)), attenuation))
+    }
+}
+
+/// Sine and cosine of the angle to the normal plane and the azimuthal angle of a local direction.
+fn angles(w: &Vec3) -> (Float, Float, Float) {
+    let sin_theta = clamp(w.x(), -1., 1.);
+    (
+        sin_theta,
+        safe_sqrt(1. - sin_theta * sin_theta),
+        w.z().atan2(w.y()),
+    )
+}
+
+fn safe_sqrt(x: Float) -> Float {
+    x.max(0.).sqrt()
+}
+
+/// The modified Bessel function of the first kind of order 0.
+fn bessel_i0(x: Float) -> Float {
+    let mut value = 0.;
+    let mut x2i = 1.;
+    let mut factorial = 1.;
+    let mut four_i = 1.;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as Float;
+        }
+        value += x2i / (four_i * factorial * factorial);
+        x2i *= x * x;
+        four_i *= 4.;
+    }
+    value
+}
+
+fn log_bessel_i0(x: Float) -> Float {
+    if x > 12. {
+        x + 0.5 * (-(2. * PI).ln() + (1. / x).ln() + 1. / (8. * x))
+    } else {
+        bessel_i0(x).ln()
+    }
+}
+
+/// The longitudinal scattering function M_p.
+fn longitudinal(
+    cos_theta_i: Float,
+    cos_theta_o: Float,
+    sin_theta_i: Float,
+    sin_theta_o: Float,
+    v: Float,
+) -> Float {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        (log_bessel_i0(a) - b - 1. / v + LN_2 + (1. / (2. * v)).ln()).exp()
+    } else {
+        (-b).exp() * bessel_i0(a) / ((1. / v).sinh() * 2. * v)
+    }
+}
+
+/// The azimuthal deflection of lobe `p`.
+fn phi(p: usize, gamma_o: Float, gamma_t: Float) -> Float {
+    let p = p as Float;
+    2. * p * gamma_t - 2. * gamma_o + p * PI
+}
+
+fn logistic(x: Float, s: Float) -> Float {
+    let e = (-x.abs() / s).exp();
+    e / (s * (1. + e) * (1. + e))
+}
+
+fn logistic_cdf(x: Float, s: Float) -> Float {
+    1. / (1. + (-x / s).exp())
+}
+
+/// The azimuthal scattering function N_p.
+fn azimuthal(phi_difference: Float, p: usize, s: Float, gamma_o: Float, gamma_t: Float) -> Float {
+    let mut dphi = phi_difference - phi(p, gamma_o, gamma_t);
+    while dphi > PI {
+        dphi -= 2. * PI;
+    }
+    while dphi < -PI {
+        dphi += 2. * PI;
+    }
+    logistic(dphi, s) / (logistic_cdf(PI, s) - logistic_cdf(-PI, s))
+}
+
+fn sample_trimmed_logistic(u: Float, s: Float, a: Float, b: Float) -> Float {
+    let k = logistic_cdf(b, s) - logistic_cdf(a, s);
+    let x = -s * (1. / (u * k + logistic_cdf(a, s)) - 1.).ln();
+    clamp(x, a, b)
+}
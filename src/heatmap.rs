@@ -0,0 +1,107 @@
+use crate::image::Image;
+use crate::math::clamp;
+use crate::math::float::Float;
+use crate::stats::TraversalStats;
+use crate::vec3::Vec3;
+
+/// Control points of the false-color palette (low to high), given in sRGB.
+const PALETTE: [(Float, Float, Float); 7] = [
+    (0.19, 0.07, 0.23),
+    (0.27, 0.42, 0.93),
+    (0.10, 0.80, 0.78),
+    (0.45, 0.99, 0.36),
+    (0.96, 0.80, 0.23),
+    (0.91, 0.30, 0.06),
+    (0.48, 0.02, 0.01),
+];
+
+const LEGEND_WIDTH: usize = 24;
+const LEGEND_MARGIN: usize = 16;
+
+/// Per-pixel traversal statistics of the primary rays.
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    width: usize,
+    height: usize,
+    stats: Vec<TraversalStats>,
+}
+
+impl Heatmap {
+    pub fn new(width: usize, height: usize) -> Heatmap {
+        Heatmap {
+            width,
+            height,
+            stats: vec![TraversalStats::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, stats: TraversalStats) {
+        self.stats[y * self.width + x] = stats;
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> TraversalStats {
+        self.stats[y * self.width + x]
+    }
+
+    /// The largest number of tests (AABB tests and primitive intersections) of any pixel.
+    pub fn max_total(&self) -> u64 {
+        self.stats
+            .iter()
+            .map(TraversalStats::total)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Maps the total number of tests of each pixel through the palette and draws a legend (low
+    /// at the bottom, `max_total()` at the top) at the right border of the image.
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::new(self.width, self.height);
+        let max = self.max_total().max(1) as Float;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                image.set_pixel(x, y, false_color(self.get(x, y).total() as Float / max));
+            }
+        }
+
+        if self.width > LEGEND_WIDTH + 2 * LEGEND_MARGIN && self.height > 2 * LEGEND_MARGIN + 2 {
+            let x0 = self.width - LEGEND_WIDTH - LEGEND_MARGIN;
+            let y0 = LEGEND_MARGIN;
+            let legend_height = self.height - 2 * LEGEND_MARGIN;
+            for y in y0..y0 + legend_height {
+                let value = (y - y0) as Float / (legend_height - 1) as Float;
+                for x in x0..x0 + LEGEND_WIDTH {
+                    let border = x == x0 || x == x0 + LEGEND_WIDTH - 1 || y == y0;
+                    let border = border || y == y0 + legend_height - 1;
+                    let color = if border {
+                        Vec3::new(1., 1., 1.)
+                    } else {
+                        false_color(value)
+                    };
+                    image.set_pixel(x, y, color);
+                }
+            }
+        }
+
+        image
+    }
+}
+
+/// Maps `value` in `[0, 1]` to a color of the palette. The returned color is linear (the image
+/// output applies a gamma of 2).
+pub fn false_color(value: Float) -> Vec3 {
+    let scaled = clamp(value, 0., 1.) * (PALETTE.len() - 1) as Float;
+    let i = (scaled.floor() as usize).min(PALETTE.len() - 2);
+    let t = scaled - i as Float;
+    let (r0, g0, b0) = PALETTE[i];
+    let (r1, g1, b1) = PALETTE[i + 1];
+    let srgb = (1. - t) * Vec3::new(r0, g0, b0) + t * Vec3::new(r1, g1, b1);
+    srgb * srgb
+}
@@ -1,8 +1,9 @@
 use std::fmt::Debug;
+use std::sync::Arc;
 
 use crate::bvh::Aabb;
 use crate::material::Material;
-use crate::math::float::Float;
+use crate::math::float::{self, Float};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 use crate::Rng;
@@ -18,6 +19,12 @@ pub struct HitRecord<'a> {
     pub v: Float,
     pub p: Vec3,
     pub normal: Vec3,
+    /// The partial derivative of the surface position with respect to `u` (the tangent used for
+    /// anisotropic shading and normal mapping). Zero if the shape doesn't provide one.
+    pub dpdu: Vec3,
+    /// The partial derivative of the surface position with respect to `v`. Zero if the shape
+    /// doesn't provide one.
+    pub dpdv: Vec3,
     pub material: &'a Material,
 }
 
@@ -53,3 +60,93 @@ impl<R: Rng> Hit<R> for [Box<dyn Hit<R>>] {
         Some(aabb)
     }
 }
+
+impl<R: Rng, T: Hit<R> + ?Sized> Hit<R> for Arc<T> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, rng: &mut R) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, t_min, t_max, rng)
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        (**self).bounding_box(time_start, time_end)
+    }
+}
+
+/// Shapes whose surface can be sampled uniformly, e.g. to use them as area lights.
+pub trait SampleSurface {
+    fn area(&self) -> Float;
+
+    /// Returns a uniformly distributed point on the surface and the unit normal at that point.
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3);
+
+    /// Samples a direction from `origin` towards the surface. Returns the direction (not
+    /// normalized, pointing to the sampled point) and its density with respect to solid angle.
+    fn sample_direction<R: Rng>(&self, origin: &Vec3, rng: &mut R) -> Option<(Vec3, Float)> {
+        let (p, normal) = self.sample_surface(rng);
+        let direction = p - *origin;
+        let distance_squared = direction.length_squared();
+        let cosine = (direction.dot(&normal) / distance_squared.sqrt()).abs();
+        if cosine < 1e-6 {
+            return None;
+        }
+        Some((direction, distance_squared / (cosine * self.area())))
+    }
+
+    /// The density with respect to solid angle of `sample_direction` returning `direction`. The
+    /// probe ray is spawned from `ray` so it keeps the time and wavelength of the path.
+    fn pdf_value<R: Rng>(&self, ray: &Ray, origin: &Vec3, direction: &Vec3, rng: &mut R) -> Float
+    where
+        Self: Hit<R>,
+    {
+        let ray = ray.spawn(*origin, *direction);
+        match self.hit(&ray, 0.001, float::MAX, rng) {
+            Some(hit_record) => {
+                let distance_squared = hit_record.t.powi(2) * direction.length_squared();
+                let cosine = (direction.dot(&hit_record.normal) / direction.length()).abs();
+                distance_squared / (cosine * self.area())
+            }
+            None => 0.,
+        }
+    }
+}
+
+/// A shape which the integrator samples directions towards (an area light). Implemented for
+/// every shape with `SampleSurface` so lights of different shapes can share one list.
+pub trait Light<R: Rng>: Sync + Send + Debug {
+    /// Samples a direction from `origin` towards the light for the path of `ray`. See
+    /// `SampleSurface::sample_direction`.
+    fn sample_direction(&self, ray: &Ray, origin: &Vec3, rng: &mut R) -> Option<(Vec3, Float)>;
+    fn pdf_value(&self, ray: &Ray, origin: &Vec3, direction: &Vec3, rng: &mut R) -> Float;
+}
+
+impl<R: Rng, T: SampleSurface + Hit<R>> Light<R> for T {
+    fn sample_direction(&self, _: &Ray, origin: &Vec3, rng: &mut R) -> Option<(Vec3, Float)> {
+        SampleSurface::sample_direction(self, origin, rng)
+    }
+
+    fn pdf_value(&self, ray: &Ray, origin: &Vec3, direction: &Vec3, rng: &mut R) -> Float {
+        SampleSurface::pdf_value(self, ray, origin, direction, rng)
+    }
+}
+
+/// Picks one of the lights uniformly; the density is the average over all lights.
+impl<R: Rng> Light<R> for [Box<dyn Light<R>>] {
+    fn sample_direction(&self, ray: &Ray, origin: &Vec3, rng: &mut R) -> Option<(Vec3, Float)> {
+        if self.is_empty() {
+            return None;
+        }
+        let light = &self[rng.gen_range(0, self.len())];
+        let (direction, _) = light.sample_direction(ray, origin, rng)?;
+        Some((direction, self.pdf_value(ray, origin, &direction, rng)))
+    }
+
+    fn pdf_value(&self, ray: &Ray, origin: &Vec3, direction: &Vec3, rng: &mut R) -> Float {
+        if self.is_empty() {
+            return 0.;
+        }
+        let sum: Float = self
+            .iter()
+            .map(|light| light.pdf_value(ray, origin, direction, rng))
+            .sum();
+        sum / self.len() as Float
+    }
+}
@@ -58,7 +58,7 @@ impl Image {
     }
 
     pub fn save_to_ppm(&self, path: &Path) -> Result<(), io::Error> {
-        let mut file = File::create(&path)?;
+        let mut file = File::create(path)?;
 
         let header = format!("P6\n{}\n{}\n255\n", self.image[0].len(), self.image.len());
         file.write_all(header.as_bytes())?;
@@ -2,15 +2,28 @@
 
 use std::fmt::Debug;
 
+pub mod animation;
 pub mod bvh;
 pub mod camera;
+pub mod hair;
+pub mod heatmap;
 pub mod hit;
 pub mod image;
 pub mod material;
 pub mod math;
+pub mod matrix;
+pub mod mesh;
+pub mod microfacet;
+pub mod onb;
 pub mod perlin;
+pub mod principled;
+pub mod quaternion;
 pub mod ray;
+pub mod sdf;
 pub mod shape;
+pub mod spectrum;
+pub mod stats;
+pub mod subsurface;
 pub mod texture;
 pub mod vec3;
 
@@ -6,23 +6,37 @@ use std::sync::{Arc, Mutex};
 
 use indicatif::{ProgressBar, ProgressStyle};
 use rand::SeedableRng;
-use rand::Rng as RandRng;
 use rand_xoshiro::Xoshiro256StarStar;
 use rayon::prelude::*;
 
 use finales_funkeln::bvh::{Bvh, BvhError};
 use finales_funkeln::camera::{Camera, CameraParameters};
-use finales_funkeln::hit::Hit;
+use finales_funkeln::heatmap::Heatmap;
+use finales_funkeln::hit::{Hit, HitRecord, Light};
 use finales_funkeln::image::Image;
 use finales_funkeln::material::*;
 use finales_funkeln::math::float::{self, Float};
+use finales_funkeln::math::{partial_max, partial_min};
 use finales_funkeln::ray::Ray;
 use finales_funkeln::shape::*;
+use finales_funkeln::spectrum;
+use finales_funkeln::stats;
+use finales_funkeln::subsurface::Subsurface;
 use finales_funkeln::texture::Texture;
 use finales_funkeln::vec3::Vec3;
 use finales_funkeln::Rng;
 
 type Prng = Xoshiro256StarStar;
+/// The objects of a scene and those of them which are sampled as lights.
+type Scene<R> = (Vec<Box<dyn Hit<R>>>, Vec<Box<dyn Light<R>>>);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RenderMode {
+    Shaded,
+    /// Colors each pixel by the number of AABB tests and primitive intersections of its primary
+    /// ray. Needs the `stats` feature.
+    Heatmap,
+}
 
 // TODO: Revamp material/texture resource managment (Scene) so we don't need `Arc`s and can share
 //       `Texture`s.
@@ -33,14 +47,23 @@ fn main() -> Result<(), Box<dyn Error>> {
     } else {
         (720, 480, 100)
     };
-    let image = Arc::new(Mutex::new(Image::new(width, height)));
+    let render_mode = if true {
+        RenderMode::Shaded
+    } else {
+        RenderMode::Heatmap
+    };
+    if render_mode == RenderMode::Heatmap && !stats::ENABLED {
+        return Err("the heatmap render mode needs the `stats` feature".into());
+    }
 
     let mut rng = Prng::from_entropy();
-    let (hit_list, camera) = if false {
+    let (hit_list, lights, camera) = if false {
         let hit_list = if true {
             vec![Box::new(random_scene(0.0, 1.0, &mut rng)?) as Box<dyn Hit<Prng>>]
-        } else {
+        } else if false {
             two_spheres()
+        } else {
+            moon()
         };
         let camera = {
             let origin = Vec3::new(13., 2., 3.);
@@ -56,14 +79,16 @@ fn main() -> Result<(), Box<dyn Error>> {
             };
             Camera::new(origin, look_at, up, parameters, time)
         };
-        (hit_list, camera)
+        (hit_list, Vec::new(), camera)
     } else {
-        let (hit_list, origin) = if true {
+        let ((hit_list, lights), origin) = if true {
             (final_scene(0., 1., &mut rng), Vec3::new(478., 278., -600.))
         } else {
             (
                 if true {
                     cornell_box()
+                } else if false {
+                    cornell_box_fence()
                 } else {
                     cornell_box_smoke()
                 },
@@ -83,18 +108,56 @@ fn main() -> Result<(), Box<dyn Error>> {
             };
             Camera::new(origin, look_at, up, parameters, time)
         };
-        (hit_list, camera)
+        (hit_list, lights, camera)
+    };
+
+    let image = match render_mode {
+        RenderMode::Shaded => render(
+            &camera,
+            &hit_list,
+            &lights,
+            width,
+            height,
+            samples_per_pixel,
+        ),
+        RenderMode::Heatmap => {
+            let heatmap = render_heatmap(&camera, &hit_list, width, height);
+            println!(
+                "Heatmap legend: 0 (bottom) to {} (top) tests per primary ray",
+                heatmap.max_total()
+            );
+            heatmap.to_image()
+        }
     };
 
-    let progress_bar = ProgressBar::new(width as u64);
+    image.save_to_ppm(Path::new("out.ppm"))?;
+
+    Ok(())
+}
+
+fn progress_bar(len: usize) -> ProgressBar {
+    let progress_bar = ProgressBar::new(len as u64);
     progress_bar.set_style(
         ProgressStyle::default_bar()
             .template("{spinner} {elapsed_precise}/{eta_precise} {wide_bar} {percent:3}%"),
     );
     progress_bar.enable_steady_tick(100);
+    progress_bar
+}
+
+fn render<R: Rng + SeedableRng>(
+    camera: &Camera,
+    world: &[Box<dyn Hit<R>>],
+    lights: &[Box<dyn Light<R>>],
+    width: usize,
+    height: usize,
+    samples_per_pixel: usize,
+) -> Image {
+    let image = Arc::new(Mutex::new(Image::new(width, height)));
+    let progress_bar = progress_bar(width);
 
     (0..width).into_par_iter().for_each(|x| {
-        let mut rng = Prng::from_entropy();
+        let mut rng = R::from_entropy();
         let mut column = Vec::with_capacity(height);
         for y in 0..height {
             let mut color_acc = Vec3::new(0., 0., 0.);
@@ -103,9 +166,19 @@ fn main() -> Result<(), Box<dyn Error>> {
                 let t = (y as Float + rng.gen::<Float>()) / height as Float;
 
                 let ray = camera.get_ray(s, t, &mut rng);
-                color_acc += color(&ray, &hit_list, 0, &mut rng);
+                let radiance = color(&ray, world, lights, 0, &mut rng);
+                color_acc += match ray.wavelength() {
+                    // Spectral radiance (in every channel) which is integrated to XYZ
+                    Some(lambda) => radiance.r() * spectrum::cie_xyz(lambda),
+                    None => radiance,
+                };
             }
-            column.push(color_acc / samples_per_pixel as Float);
+            let color_acc = color_acc / samples_per_pixel as Float;
+            column.push(if cfg!(feature = "spectral") {
+                spectrum::xyz_to_rgb(&color_acc)
+            } else {
+                color_acc
+            });
         }
         let mut image = image.lock().unwrap();
         for (y, p) in column.iter().enumerate() {
@@ -115,32 +188,138 @@ fn main() -> Result<(), Box<dyn Error>> {
     });
     progress_bar.finish();
 
-    image.lock().unwrap().save_to_ppm(Path::new("out.ppm"))?;
+    Arc::try_unwrap(image).unwrap().into_inner().unwrap()
+}
 
-    Ok(())
+fn render_heatmap<R: Rng + SeedableRng>(
+    camera: &Camera,
+    world: &[Box<dyn Hit<R>>],
+    width: usize,
+    height: usize,
+) -> Heatmap {
+    let heatmap = Arc::new(Mutex::new(Heatmap::new(width, height)));
+    let progress_bar = progress_bar(width);
+
+    (0..width).into_par_iter().for_each(|x| {
+        let mut rng = R::from_entropy();
+        let mut column = Vec::with_capacity(height);
+        for y in 0..height {
+            // A single primary ray through the pixel center
+            let s = (x as Float + 0.5) / width as Float;
+            let t = (y as Float + 0.5) / height as Float;
+
+            let ray = camera.get_ray(s, t, &mut rng);
+            stats::take();
+            world.hit(&ray, 0.001, float::MAX, &mut rng);
+            column.push(stats::take());
+        }
+        let mut heatmap = heatmap.lock().unwrap();
+        for (y, s) in column.into_iter().enumerate() {
+            heatmap.set(x, y, s);
+        }
+        progress_bar.inc(1);
+    });
+    progress_bar.finish();
+
+    Arc::try_unwrap(heatmap).unwrap().into_inner().unwrap()
 }
 
-fn color<R: Rng>(ray: &Ray, world: &[Box<dyn Hit<R>>], depth: usize, rng: &mut R) -> Vec3 {
-    // Set t_min to a value slightly above 0 to prevent "shadow acne"
-    match world.hit(ray, 0.001, float::MAX, rng) {
-        None => Vec3::zero(),
-        Some(hit_record) => {
-            let emitted = hit_record
-                .material
-                .emit(hit_record.u, hit_record.v, &hit_record.p);
-            match (
-                depth < 50,
-                hit_record.material.scatter(ray, &hit_record, rng),
-            ) {
-                (true, Some((scattered, attenuation))) => {
-                    emitted + attenuation * color(&scattered, world, depth + 1, rng)
-                }
-                _ => emitted,
+/// Steps of random walks through a volume (see `MaterialTrait::is_walk_step`) after which Russian
+/// roulette may end the walk.
+const MIN_WALK_STEPS: usize = 64;
+/// Walks are cut off after this many steps even if they survive the roulette.
+const MAX_WALK_STEPS: usize = 1 << 16;
+
+fn color<R: Rng>(
+    ray: &Ray,
+    world: &[Box<dyn Hit<R>>],
+    lights: &[Box<dyn Light<R>>],
+    depth: usize,
+    rng: &mut R,
+) -> Vec3 {
+    // Random walks (e.g. subsurface scattering) take hundreds of steps so they are continued in
+    // this loop instead of recursing and don't count against `depth`.
+    let mut ray = *ray;
+    let mut radiance = Vec3::zero();
+    let mut throughput = Vec3::new(1., 1., 1.);
+    let mut walk_steps = 0;
+    loop {
+        // Set t_min to a value slightly above 0 to prevent "shadow acne"
+        let hit_record = match world.hit(&ray, 0.001, float::MAX, rng) {
+            None => return radiance,
+            Some(hit_record) => hit_record,
+        };
+        radiance += throughput * hit_record.material.emit(&ray, &hit_record);
+        let (scattered, attenuation) = match (
+            depth < 50,
+            hit_record.material.scatter(&ray, &hit_record, rng),
+        ) {
+            (true, Some(scattering)) => scattering,
+            _ => return radiance,
+        };
+        let (scattered, attenuation) =
+            sample_lights(&ray, &hit_record, lights, scattered, attenuation, rng);
+        // Attenuations which were computed before the path got a wavelength (e.g. by a dispersive
+        // dielectric) stay in RGB.
+        let attenuation = spectrum::at_wavelength(&attenuation, ray.wavelength());
+        if !hit_record.material.is_walk_step(&ray, &hit_record) {
+            return radiance
+                + throughput * attenuation * color(&scattered, world, lights, depth + 1, rng);
+        }
+
+        throughput *= attenuation;
+        walk_steps += 1;
+        if walk_steps > MIN_WALK_STEPS {
+            let survival = partial_min(
+                partial_max(throughput.r(), partial_max(throughput.g(), throughput.b())),
+                1.,
+            );
+            if walk_steps > MAX_WALK_STEPS || rng.gen::<Float>() >= survival {
+                return radiance;
             }
+            throughput /= survival;
         }
+        ray = scattered;
     }
 }
 
+/// For diffuse materials half of the scattered rays are sent towards the lights instead. Both
+/// kinds are weighted with the density of the mixture of the two strategies so the estimate stays
+/// unbiased while small lights are found far more often than by chance.
+fn sample_lights<R: Rng>(
+    ray: &Ray,
+    hit_record: &HitRecord<'_>,
+    lights: &[Box<dyn Light<R>>],
+    scattered: Ray,
+    attenuation: Vec3,
+    rng: &mut R,
+) -> (Ray, Vec3) {
+    let diffuse = hit_record
+        .material
+        .evaluate(ray, hit_record, &scattered.direction())
+        .is_some();
+    if lights.is_empty() || !diffuse {
+        return (scattered, attenuation);
+    }
+    let direction = if rng.gen::<bool>() {
+        match lights.sample_direction(ray, &hit_record.p, rng) {
+            Some((direction, _)) => direction,
+            // The light sample failed so this half of the estimate is zero.
+            None => return (scattered, Vec3::zero()),
+        }
+    } else {
+        scattered.direction()
+    };
+    let (value, material_pdf) = hit_record
+        .material
+        .evaluate(ray, hit_record, &direction)
+        .unwrap();
+    let light_pdf = lights.pdf_value(ray, &hit_record.p, &direction, rng);
+    let pdf = 0.5 * material_pdf + 0.5 * light_pdf;
+    let attenuation = if pdf > 0. { value / pdf } else { Vec3::zero() };
+    (ray.spawn(hit_record.p, direction), attenuation)
+}
+
 fn random_scene<R: Rng>(
     time_start: Float,
     time_end: Float,
@@ -227,13 +406,14 @@ fn random_scene<R: Rng>(
     Bvh::new(list, time_start, time_end, rng)
 }
 
-fn cornell_box<R: Rng>() -> Vec<Box<dyn Hit<R>>> {
+fn cornell_box<R: Rng>() -> Scene<R> {
     let mut vec: Vec<Box<dyn Hit<R>>> = Vec::new();
 
     let red = Material::lambertian(Texture::constant(Vec3::new(0.65, 0.05, 0.05)));
     let white = Material::lambertian(Texture::constant(Vec3::new(0.73, 0.73, 0.73)));
     let green = Material::lambertian(Texture::constant(Vec3::new(0.12, 0.45, 0.15)));
-    let light = Material::diffuse_light(Texture::constant(Vec3::new(15., 15., 15.)));
+    let light =
+        Material::light(Emission::new(Texture::constant(Vec3::new(15., 15., 15.))).one_sided());
 
     const W: Float = 555.;
 
@@ -241,12 +421,14 @@ fn cornell_box<R: Rng>() -> Vec<Box<dyn Hit<R>>> {
         YZRect::new((0., W), (0., W), W, green).flip_normals(),
     ));
     vec.push(Box::new(YZRect::new((0., W), (0., W), 0., red)));
-    vec.push(Box::new(XZRect::new(
-        (213., 343.),
-        (227., 332.),
-        W - 1.,
+    // Facing down into the box (u × v points down)
+    let light = Quad::new(
+        Vec3::new(213., W - 1., 227.),
+        Vec3::new(130., 0., 0.),
+        Vec3::new(0., 0., 105.),
         light,
-    )));
+    );
+    vec.push(Box::new(light.clone()));
     vec.push(Box::new(
         XZRect::new((0., W), (0., W), W, Arc::clone(&white)).flip_normals(),
     ));
@@ -279,16 +461,37 @@ fn cornell_box<R: Rng>() -> Vec<Box<dyn Hit<R>>> {
         .translate(Vec3::new(265., 0., 295.)),
     ));
 
-    vec
+    (vec, vec![Box::new(light)])
+}
+
+/// The Cornell box with a fence panel in front of the tall box, facing the camera. The checker
+/// board cuts out every other square.
+fn cornell_box_fence<R: Rng>() -> Scene<R> {
+    let (mut vec, lights) = cornell_box();
+
+    let white = Material::lambertian(Texture::constant(Vec3::new(0.73, 0.73, 0.73)));
+    vec.push(Box::new(AlphaMask::new(
+        Quad::new(
+            Vec3::new(330., 0., 100.),
+            Vec3::new(0., 180., 0.),
+            Vec3::new(200., 0., 0.),
+            white,
+        ),
+        Texture::checker_board(Texture::scalar(0.), Texture::scalar(1.), 5.),
+        0.5,
+    )));
+
+    (vec, lights)
 }
 
-fn cornell_box_smoke<R: Rng>() -> Vec<Box<dyn Hit<R>>> {
+fn cornell_box_smoke<R: Rng>() -> Scene<R> {
     let mut vec: Vec<Box<dyn Hit<R>>> = Vec::new();
 
     let red = Material::lambertian(Texture::constant(Vec3::new(0.65, 0.05, 0.05)));
     let white = Material::lambertian(Texture::constant(Vec3::new(0.73, 0.73, 0.73)));
     let green = Material::lambertian(Texture::constant(Vec3::new(0.12, 0.45, 0.15)));
-    let light = Material::diffuse_light(Texture::constant(Vec3::new(7., 7., 7.)));
+    let light =
+        Material::light(Emission::new(Texture::constant(Vec3::new(7., 7., 7.))).one_sided());
 
     const W: Float = 555.;
 
@@ -296,12 +499,14 @@ fn cornell_box_smoke<R: Rng>() -> Vec<Box<dyn Hit<R>>> {
         YZRect::new((0., W), (0., W), W, green).flip_normals(),
     ));
     vec.push(Box::new(YZRect::new((0., W), (0., W), 0., red)));
-    vec.push(Box::new(XZRect::new(
-        (213., 343.),
-        (227., 332.),
-        W - 1.,
+    // Facing down into the box (u × v points down)
+    let light = Quad::new(
+        Vec3::new(213., W - 1., 227.),
+        Vec3::new(130., 0., 0.),
+        Vec3::new(0., 0., 105.),
         light,
-    )));
+    );
+    vec.push(Box::new(light.clone()));
     vec.push(Box::new(
         XZRect::new((0., W), (0., W), W, Arc::clone(&white)).flip_normals(),
     ));
@@ -342,7 +547,7 @@ fn cornell_box_smoke<R: Rng>() -> Vec<Box<dyn Hit<R>>> {
         Texture::constant(Vec3::new(0.0, 0.0, 0.0)),
     )));
 
-    vec
+    (vec, vec![Box::new(light)])
 }
 
 fn two_spheres<R: Rng>() -> Vec<Box<dyn Hit<R>>> {
@@ -371,7 +576,40 @@ fn two_spheres<R: Rng>() -> Vec<Box<dyn Hit<R>>> {
     vec
 }
 
-fn final_scene<R: Rng>(time_start: Float, time_end: Float, rng: &mut R) -> Vec<Box<dyn Hit<R>>> {
+/// A rough Oren-Nayar sphere next to a Lambertian one. The moon's regolith is much rougher than
+/// Lambertian; it looks flat under the light.
+fn moon<R: Rng>() -> Vec<Box<dyn Hit<R>>> {
+    let mut vec: Vec<Box<dyn Hit<R>>> = Vec::new();
+
+    let light = Material::diffuse_light(Texture::constant(Vec3::new(1., 1., 1.)));
+    let grey = Vec3::new(0.6, 0.6, 0.6);
+
+    vec.push(Box::new(XZRect::new(
+        (-100., 100.),
+        (-100., 100.),
+        150.,
+        light,
+    )));
+    vec.push(Box::new(Sphere::new(
+        Vec3::new(0., -1000., 0.),
+        1000.,
+        Material::lambertian(Texture::noise(4.)),
+    )));
+    vec.push(Box::new(Sphere::new(
+        Vec3::new(0., 2., -2.5),
+        2.,
+        Material::oren_nayar(Texture::constant(grey), 30.),
+    )));
+    vec.push(Box::new(Sphere::new(
+        Vec3::new(0., 2., 2.5),
+        2.,
+        Material::lambertian(Texture::constant(grey)),
+    )));
+
+    vec
+}
+
+fn final_scene<R: Rng>(time_start: Float, time_end: Float, rng: &mut R) -> Scene<R> {
     let mut vec: Vec<Box<dyn Hit<R>>> = Vec::new();
 
     let white = Material::lambertian(Texture::constant(Vec3::new(0.73, 0.73, 0.73)));
@@ -400,12 +638,13 @@ fn final_scene<R: Rng>(time_start: Float, time_end: Float, rng: &mut R) -> Vec<B
         Bvh::new(ground, time_start, time_end, rng).unwrap(),
     ));
 
-    vec.push(Box::new(XZRect::new(
-        (123., 423.),
-        (147., 412.),
-        553.,
+    let light = Quad::new(
+        Vec3::new(123., 553., 147.),
+        Vec3::new(300., 0., 0.),
+        Vec3::new(0., 0., 265.),
         light,
-    )));
+    );
+    vec.push(Box::new(light.clone()));
 
     vec.push(Box::new(Sphere::new_moving(
         Vec3::new(400., 400., 200.),
@@ -426,15 +665,12 @@ fn final_scene<R: Rng>(time_start: Float, time_end: Float, rng: &mut R) -> Vec<B
         Material::metal(Vec3::new(0.8, 0.8, 0.9), 1.),
     )));
 
-    // TODO: Our Rust version currently needs to clone the inner hit object of a ConstantMedium
-    //       while the C version just stores a pointer to it. Probably related to the resource
-    //       managment TODO.
-    let subsurface = Sphere::new(Vec3::new(360., 150., 145.), 70., Material::dielectric(1.5));
-    vec.push(Box::new(subsurface.clone()));
-    vec.push(Box::new(ConstantMedium::new(
-        subsurface,
-        0.2,
-        Texture::constant(Vec3::new(0.2, 0.4, 0.9)),
+    vec.push(Box::new(Sphere::new(
+        Vec3::new(360., 150., 145.),
+        70.,
+        Material::subsurface(
+            Subsurface::new(Vec3::new(0.2, 0.4, 0.9), Vec3::new(5., 5., 5.)).with_ior(1.5),
+        ),
     )));
 
     vec.push(Box::new(ConstantMedium::new(
@@ -476,5 +712,5 @@ fn final_scene<R: Rng>(time_start: Float, time_end: Float, rng: &mut R) -> Vec<B
             .translate(Vec3::new(-100., 270., 395.)),
     ));
 
-    vec
+    (vec, vec![Box::new(light)])
 }
@@ -1,8 +1,16 @@
 use std::sync::Arc;
 
+use crate::hair::Hair;
 use crate::hit::HitRecord;
+use crate::math::float::consts::PI;
 use crate::math::float::Float;
+use crate::math::{clamp, partial_max};
+use crate::microfacet::{self, fresnel_conductor, ComplexIor, Ggx};
+use crate::onb::Onb;
+use crate::principled::Principled;
 use crate::ray::Ray;
+use crate::spectrum::{self, Ior, Spd};
+use crate::subsurface::Subsurface;
 use crate::texture::Sample;
 use crate::texture::Texture;
 use crate::vec3::Vec3;
@@ -16,19 +24,53 @@ pub trait MaterialTrait {
         rng: &mut R,
     ) -> Option<(Ray, Vec3)>;
 
+    /// The radiance emitted towards the origin of `ray`. For rays with a wavelength every channel
+    /// is the spectral radiance at it (see `spectrum::at_wavelength`).
     #[allow(unused_variables)]
-    fn emit(&self, u: Float, v: Float, p: &Vec3) -> Vec3 {
+    fn emit(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> Vec3 {
         Vec3::zero()
     }
+
+    /// Whether scattering `ray` at `hit_record` is a step of a random walk through the volume of
+    /// the material instead of a bounce off its surface. These steps don't count against the path
+    /// depth of the integrator.
+    #[allow(unused_variables)]
+    fn is_walk_step(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> bool {
+        false
+    }
+
+    /// The BSDF times the cosine for scattering `ray` into `direction` and the density (with
+    /// respect to solid angle) with which `scatter` samples that direction. Only diffuse materials
+    /// implement this so the integrator can mix their sampling with light sampling; `None`
+    /// otherwise.
+    #[allow(unused_variables)]
+    fn evaluate(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        direction: &Vec3,
+    ) -> Option<(Vec3, Float)> {
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum Material {
     Lambertian(Lambertian),
+    OrenNayar(OrenNayar),
     Metal(Metal),
+    Conductor(Conductor),
     Dielectric(Dielectric),
+    RoughDielectric(RoughDielectric),
     DiffuseLight(DiffuseLight),
+    Emissive(Emissive),
     Isotropic(Isotropic),
+    Hair(Hair),
+    Principled(Box<Principled>),
+    Subsurface(Subsurface),
+    Mix(Mix),
+    Coated(Coated),
+    Bump(Bump),
 }
 
 impl Material {
@@ -36,18 +78,107 @@ impl Material {
         Arc::new(Material::Lambertian(Lambertian::new(texture)))
     }
 
+    /// A rough diffuse surface. See `OrenNayar`.
+    pub fn oren_nayar(texture: Texture, sigma: Float) -> Arc<Material> {
+        Arc::new(Material::OrenNayar(OrenNayar::new(texture, sigma)))
+    }
+
     pub fn metal(albedo: Vec3, fuzz: Float) -> Arc<Material> {
         Arc::new(Material::Metal(Metal::new(albedo, fuzz)))
     }
 
+    /// A GGX microfacet metal. See `Conductor`.
+    pub fn conductor(ior: ComplexIor, roughness: Float) -> Arc<Material> {
+        Arc::new(Material::Conductor(Conductor::new(ior, roughness)))
+    }
+
+    pub fn anisotropic_conductor(
+        ior: ComplexIor,
+        roughness_u: Float,
+        roughness_v: Float,
+    ) -> Arc<Material> {
+        Arc::new(Material::Conductor(Conductor::anisotropic(
+            ior,
+            roughness_u,
+            roughness_v,
+        )))
+    }
+
     pub fn dielectric(ref_idx: Float) -> Arc<Material> {
         Arc::new(Material::Dielectric(Dielectric::new(ref_idx)))
     }
 
+    /// Tinted glass which transmits `color` after light traveled `distance` inside.
+    pub fn colored_dielectric(ref_idx: Float, color: Vec3, distance: Float) -> Arc<Material> {
+        Arc::new(Material::Dielectric(
+            Dielectric::new(ref_idx).with_color(color, distance),
+        ))
+    }
+
+    /// Glass whose index of refraction depends on the wavelength, e.g. `Ior::bk7()`.
+    pub fn dispersive_dielectric(ior: Ior) -> Arc<Material> {
+        Arc::new(Material::Dielectric(Dielectric::from_ior(ior)))
+    }
+
+    /// Frosted glass. See `RoughDielectric`.
+    pub fn rough_dielectric(ref_idx: Float, roughness: Texture) -> Arc<Material> {
+        Arc::new(Material::RoughDielectric(RoughDielectric::new(
+            ref_idx, roughness,
+        )))
+    }
+
     pub fn diffuse_light(texture: Texture) -> Arc<Material> {
         Arc::new(Material::DiffuseLight(DiffuseLight::new(texture)))
     }
 
+    /// A light emitting `spd` (e.g. `Spd::blackbody(6500.)`) scaled by the texture.
+    pub fn spectral_light(texture: Texture, spd: Spd) -> Arc<Material> {
+        Material::light(Emission::new(texture).with_spd(spd))
+    }
+
+    pub fn light(emission: Emission) -> Arc<Material> {
+        Arc::new(Material::DiffuseLight(DiffuseLight::from_emission(
+            emission,
+        )))
+    }
+
+    /// `base` which additionally emits. See `Emissive`.
+    pub fn emissive(base: Arc<Material>, emission: Emission) -> Arc<Material> {
+        Arc::new(Material::Emissive(Emissive::new(base, emission)))
+    }
+
+    pub fn hair(hair: Hair) -> Arc<Material> {
+        Arc::new(Material::Hair(hair))
+    }
+
+    pub fn principled(principled: Principled) -> Arc<Material> {
+        Arc::new(Material::Principled(Box::new(principled)))
+    }
+
+    pub fn subsurface(subsurface: Subsurface) -> Arc<Material> {
+        Arc::new(Material::Subsurface(subsurface))
+    }
+
+    /// See `Mix`.
+    pub fn mix(first: Arc<Material>, second: Arc<Material>, weight: Texture) -> Arc<Material> {
+        Arc::new(Material::Mix(Mix::new(first, second, weight)))
+    }
+
+    /// A smooth clearcoat over `base`. See `Coated`.
+    pub fn coated(base: Arc<Material>, ref_idx: Float) -> Arc<Material> {
+        Arc::new(Material::Coated(Coated::new(base, ref_idx)))
+    }
+
+    /// `base` with a tangent space normal map. See `Bump`.
+    pub fn normal_mapped(base: Arc<Material>, normals: Texture) -> Arc<Material> {
+        Arc::new(Material::Bump(Bump::normal_map(base, normals)))
+    }
+
+    /// `base` with a height map (e.g. `Texture::noise`). See `Bump`.
+    pub fn bump_mapped(base: Arc<Material>, height: Texture, scale: Float) -> Arc<Material> {
+        Arc::new(Material::Bump(Bump::height_map(base, height, scale)))
+    }
+
     // Isotropic is only used by ConstantMedium which doesn't share it's phase function (material)
     // with others so we don't need the `Arc<T>`.
     pub fn isotropic(albedo: Texture) -> Material {
@@ -64,20 +195,64 @@ impl MaterialTrait for Material {
     ) -> Option<(Ray, Vec3)> {
         match self {
             Material::Lambertian(lambertian) => lambertian.scatter(ray, hit_record, rng),
+            Material::OrenNayar(oren_nayar) => oren_nayar.scatter(ray, hit_record, rng),
             Material::Metal(metal) => metal.scatter(ray, hit_record, rng),
+            Material::Conductor(conductor) => conductor.scatter(ray, hit_record, rng),
             Material::Dielectric(dielectric) => dielectric.scatter(ray, hit_record, rng),
+            Material::RoughDielectric(rough_dielectric) => {
+                rough_dielectric.scatter(ray, hit_record, rng)
+            }
             Material::DiffuseLight(diffuse_light) => diffuse_light.scatter(ray, hit_record, rng),
+            Material::Emissive(emissive) => emissive.scatter(ray, hit_record, rng),
             Material::Isotropic(isotropic) => isotropic.scatter(ray, hit_record, rng),
+            Material::Hair(hair) => hair.scatter(ray, hit_record, rng),
+            Material::Principled(principled) => principled.scatter(ray, hit_record, rng),
+            Material::Subsurface(subsurface) => subsurface.scatter(ray, hit_record, rng),
+            Material::Mix(mix) => mix.scatter(ray, hit_record, rng),
+            Material::Coated(coated) => coated.scatter(ray, hit_record, rng),
+            Material::Bump(bump) => bump.scatter(ray, hit_record, rng),
+        }
+    }
+
+    fn emit(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> Vec3 {
+        match self {
+            Material::Lambertian(lambertian) => lambertian.emit(ray, hit_record),
+            Material::OrenNayar(oren_nayar) => oren_nayar.emit(ray, hit_record),
+            Material::Metal(metal) => metal.emit(ray, hit_record),
+            Material::Conductor(conductor) => conductor.emit(ray, hit_record),
+            Material::Dielectric(dielectric) => dielectric.emit(ray, hit_record),
+            Material::RoughDielectric(rough_dielectric) => rough_dielectric.emit(ray, hit_record),
+            Material::DiffuseLight(diffuse_light) => diffuse_light.emit(ray, hit_record),
+            Material::Emissive(emissive) => emissive.emit(ray, hit_record),
+            Material::Isotropic(isotropic) => isotropic.emit(ray, hit_record),
+            Material::Hair(hair) => hair.emit(ray, hit_record),
+            Material::Principled(principled) => principled.emit(ray, hit_record),
+            Material::Subsurface(subsurface) => subsurface.emit(ray, hit_record),
+            Material::Mix(mix) => mix.emit(ray, hit_record),
+            Material::Coated(coated) => coated.emit(ray, hit_record),
+            Material::Bump(bump) => bump.emit(ray, hit_record),
         }
     }
 
-    fn emit(&self, u: Float, v: Float, p: &Vec3) -> Vec3 {
+    fn is_walk_step(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> bool {
+        match self {
+            Material::Subsurface(subsurface) => subsurface.is_walk_step(ray, hit_record),
+            Material::Emissive(emissive) => emissive.is_walk_step(ray, hit_record),
+            _ => false,
+        }
+    }
+
+    fn evaluate(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        direction: &Vec3,
+    ) -> Option<(Vec3, Float)> {
         match self {
-            Material::Lambertian(lambertian) => lambertian.emit(u, v, p),
-            Material::Metal(metal) => metal.emit(u, v, p),
-            Material::Dielectric(dielectric) => dielectric.emit(u, v, p),
-            Material::DiffuseLight(diffuse_light) => diffuse_light.emit(u, v, p),
-            Material::Isotropic(isotropic) => isotropic.emit(u, v, p),
+            Material::Lambertian(lambertian) => lambertian.evaluate(ray, hit_record, direction),
+            Material::OrenNayar(oren_nayar) => oren_nayar.evaluate(ray, hit_record, direction),
+            Material::Emissive(emissive) => emissive.evaluate(ray, hit_record, direction),
+            _ => None,
         }
     }
 }
@@ -100,14 +275,124 @@ impl MaterialTrait for Lambertian {
         hit_record: &HitRecord<'_>,
         rng: &mut R,
     ) -> Option<(Ray, Vec3)> {
-        let target: Vec3 = hit_record.p + hit_record.normal + random_in_unit_sphere(rng);
-        let scattered: Ray = Ray::new(hit_record.p, target - hit_record.p, ray.time());
+        // The cosine and 1 / pi of the BRDF cancel with the density of the sampling.
+        let frame = Onb::from_w(&hit_record.normal);
+        let direction = frame.to_world(&random_cosine_direction(rng));
+        let scattered: Ray = ray.spawn(hit_record.p, direction);
         Some((
             scattered,
             self.texture
                 .sample(hit_record.u, hit_record.v, &hit_record.p),
         ))
     }
+
+    fn evaluate(
+        &self,
+        _: &Ray,
+        hit_record: &HitRecord<'_>,
+        direction: &Vec3,
+    ) -> Option<(Vec3, Float)> {
+        let cosine = partial_max(hit_record.normal.dot(&direction.unit_vector()), 0.);
+        let albedo = self
+            .texture
+            .sample(hit_record.u, hit_record.v, &hit_record.p);
+        Some((albedo * cosine / PI, cosine / PI))
+    }
+}
+
+/// A rough diffuse surface (clay, concrete, the moon) whose microfacets are Lambertian, using the
+/// qualitative model of Oren and Nayar, "Generalization of Lambert's Reflectance Model" (1994).
+/// Compared to `Lambertian` it's brighter towards the light and flatter at grazing angles.
+#[derive(Debug, Clone)]
+pub struct OrenNayar {
+    texture: Texture,
+    a: Float,
+    b: Float,
+}
+
+impl OrenNayar {
+    /// `sigma` is the standard deviation of the microfacet angles in degrees; 0 is Lambertian.
+    pub fn new(texture: Texture, sigma: Float) -> OrenNayar {
+        let sigma2 = sigma.to_radians().powi(2);
+        OrenNayar {
+            texture,
+            a: 1. - sigma2 / (2. * (sigma2 + 0.33)),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+}
+
+impl OrenNayar {
+    /// The BRDF relative to a Lambertian one (`1 / pi`) for the local directions `wo` and `wi`.
+    fn weight(&self, wo: &Vec3, wi: &Vec3) -> Float {
+        let sin_theta = |w: &Vec3| (1. - w.z() * w.z()).max(0.).sqrt();
+        let (sin_theta_i, sin_theta_o) = (sin_theta(wi), sin_theta(wo));
+        let cos_phi_difference = if sin_theta_i > 1e-4 && sin_theta_o > 1e-4 {
+            partial_max(
+                (wi.x() * wo.x() + wi.y() * wo.y()) / (sin_theta_i * sin_theta_o),
+                0.,
+            )
+        } else {
+            0.
+        };
+        let (sin_alpha, tan_beta) = if wi.z() > wo.z() {
+            (sin_theta_o, sin_theta_i / wi.z())
+        } else {
+            (sin_theta_i, sin_theta_o / wo.z().max(1e-4))
+        };
+        self.a + self.b * cos_phi_difference * sin_alpha * tan_beta
+    }
+
+    /// The shading frame around the normal on the side of the incoming ray.
+    fn frame(ray: &Ray, hit_record: &HitRecord<'_>) -> Onb {
+        let normal = if ray.direction().dot(&hit_record.normal) > 0. {
+            -hit_record.normal
+        } else {
+            hit_record.normal
+        };
+        Onb::from_w(&normal)
+    }
+}
+
+impl MaterialTrait for OrenNayar {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        let frame = OrenNayar::frame(ray, hit_record);
+        let wo = frame.to_local(&-ray.direction().unit_vector());
+        let wi = random_cosine_direction(rng);
+        // As for `Lambertian` the cosine and 1 / pi cancel with the density of the sampling.
+        let weight = self.weight(&wo, &wi);
+        let albedo = self
+            .texture
+            .sample(hit_record.u, hit_record.v, &hit_record.p);
+        Some((
+            ray.spawn(hit_record.p, frame.to_world(&wi)),
+            weight * albedo,
+        ))
+    }
+
+    fn evaluate(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        direction: &Vec3,
+    ) -> Option<(Vec3, Float)> {
+        let frame = OrenNayar::frame(ray, hit_record);
+        let wo = frame.to_local(&-ray.direction().unit_vector());
+        let wi = frame.to_local(&direction.unit_vector());
+        if wi.z() <= 0. {
+            return Some((Vec3::zero(), 0.));
+        }
+        let albedo = self
+            .texture
+            .sample(hit_record.u, hit_record.v, &hit_record.p);
+        let pdf = wi.z() / PI;
+        Some((self.weight(&wo, &wi) * pdf * albedo, pdf))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -131,10 +416,9 @@ impl MaterialTrait for Metal {
         rng: &mut R,
     ) -> Option<(Ray, Vec3)> {
         let reflected = reflect(&ray.direction().unit_vector(), &hit_record.normal);
-        let scattered = Ray::new(
+        let scattered = ray.spawn(
             hit_record.p,
             reflected + self.fuzz * random_in_unit_sphere(rng),
-            ray.time(),
         );
         if scattered.direction().dot(&hit_record.normal) > 0.0 {
             Some((scattered, self.albedo))
@@ -144,14 +428,98 @@ impl MaterialTrait for Metal {
     }
 }
 
+/// A metal whose surface consists of GGX distributed microfacets which reflect the Fresnel
+/// reflectance of its complex index of refraction. A roughness of 0 is a perfect mirror.
+///
+/// The roughness can differ along the tangent (`dpdu`) and the bitangent for brushed metals.
+/// Both sides of a surface reflect.
+#[derive(Debug, Clone)]
+pub struct Conductor {
+    ior: ComplexIor,
+    distribution: Ggx,
+}
+
+impl Conductor {
+    pub fn new(ior: ComplexIor, roughness: Float) -> Conductor {
+        Conductor::anisotropic(ior, roughness, roughness)
+    }
+
+    pub fn anisotropic(ior: ComplexIor, roughness_u: Float, roughness_v: Float) -> Conductor {
+        Conductor {
+            ior,
+            distribution: Ggx::from_roughness(roughness_u, roughness_v),
+        }
+    }
+}
+
+impl MaterialTrait for Conductor {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        let normal = if ray.direction().dot(&hit_record.normal) > 0. {
+            -hit_record.normal
+        } else {
+            hit_record.normal
+        };
+        let frame = Onb::from_w_and_tangent(&normal, &hit_record.dpdu);
+        let wo = frame.to_local(&-ray.direction().unit_vector());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let m = self.distribution.sample_visible_normal(&wo, rng);
+        let wi = microfacet::reflect(&wo, &m);
+        if wi.z() <= 0. {
+            return None;
+        }
+        // The sampling density cancels D and the cosines of the BRDF.
+        let attenuation = fresnel_conductor(wo.dot(&m), &self.ior)
+            * (self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo));
+        Some((ray.spawn(hit_record.p, frame.to_world(&wi)), attenuation))
+    }
+}
+
+/// Smooth glass. Light inside is absorbed following the Beer-Lambert law; the absorption is
+/// applied when a ray leaves, using the distance from where it entered (so nothing else may be
+/// inside).
+///
+/// A dispersive index of refraction is evaluated at the wavelength of the ray. Rays without one
+/// pick a wavelength here (hero wavelength sampling) whose color weights the path from then on.
 #[derive(Debug, Clone)]
 pub struct Dielectric {
-    ref_idx: Float,
+    ior: Ior,
+    absorption: Vec3,
 }
 
 impl Dielectric {
     pub fn new(ref_idx: Float) -> Dielectric {
-        Dielectric { ref_idx }
+        Dielectric::from_ior(Ior::Constant(ref_idx))
+    }
+
+    pub fn from_ior(ior: Ior) -> Dielectric {
+        Dielectric {
+            ior,
+            absorption: Vec3::zero(),
+        }
+    }
+
+    /// The absorption coefficient (per unit of distance) for each color channel.
+    pub fn with_absorption(mut self, absorption: Vec3) -> Dielectric {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Absorbs so that `color` is transmitted after traveling `distance` inside.
+    pub fn with_color(self, color: Vec3, distance: Float) -> Dielectric {
+        let coefficient = |c: Float| -c.max(1e-6).ln() / distance;
+        self.with_absorption(Vec3::new(
+            coefficient(color.r()),
+            coefficient(color.g()),
+            coefficient(color.b()),
+        ))
     }
 }
 
@@ -162,29 +530,50 @@ impl MaterialTrait for Dielectric {
         hit_record: &HitRecord<'_>,
         rng: &mut R,
     ) -> Option<(Ray, Vec3)> {
+        let mut r_in = *r_in;
+        let mut dispersion = Vec3::new(1.0, 1.0, 1.0);
+        let ref_idx = match r_in.wavelength() {
+            Some(lambda) => self.ior.at(lambda),
+            None if self.ior.is_dispersive() => {
+                let lambda = spectrum::sample_wavelength(rng);
+                dispersion = spectrum::wavelength_to_rgb(lambda);
+                r_in = r_in.with_wavelength(Some(lambda));
+                self.ior.at(lambda)
+            }
+            None => self.ior.nominal(),
+        };
         let mut refracted: Vec3 = Vec3::zero();
         let reflect_prob: Float;
         let reflected = reflect(&r_in.direction(), &hit_record.normal);
-        let attenuation = Vec3::new(1.0, 1.0, 1.0);
-        let (outward_normal, ni_over_nt, cosine) = if r_in.direction().dot(&hit_record.normal) > 0.0
-        {
+        let inside = r_in.direction().dot(&hit_record.normal) > 0.0;
+        let attenuation = if inside {
+            // The ray started where it entered so this is the distance it traveled inside.
+            let distance = hit_record.t * r_in.direction().length();
+            Vec3::new(
+                (-self.absorption.r() * distance).exp(),
+                (-self.absorption.g() * distance).exp(),
+                (-self.absorption.b() * distance).exp(),
+            )
+        } else {
+            Vec3::new(1.0, 1.0, 1.0)
+        };
+        let (outward_normal, ni_over_nt, cosine) = if inside {
             (
                 -hit_record.normal,
-                self.ref_idx,
-                (self.ref_idx * r_in.direction().dot(&hit_record.normal)
-                    / r_in.direction().length()),
+                ref_idx,
+                (ref_idx * r_in.direction().dot(&hit_record.normal) / r_in.direction().length()),
             )
         } else {
             (
                 hit_record.normal,
-                1.0 / self.ref_idx,
+                1.0 / ref_idx,
                 (-r_in.direction().dot(&hit_record.normal) / r_in.direction().length()),
             )
         };
         let refr = refract(&r_in.direction(), &outward_normal, ni_over_nt);
         match refr {
             Some(refr) => {
-                reflect_prob = schlick(cosine, self.ref_idx);
+                reflect_prob = schlick(cosine, ref_idx);
                 refracted = refr
             }
             None => reflect_prob = 1.0,
@@ -194,18 +583,134 @@ impl MaterialTrait for Dielectric {
         } else {
             refracted
         };
-        Some((Ray::new(hit_record.p, direction, r_in.time()), attenuation))
+        Some((
+            r_in.spawn(hit_record.p, direction),
+            dispersion * attenuation,
+        ))
     }
 }
 
+/// A dielectric with a rough surface of GGX distributed microfacets which reflect and refract
+/// (Walter et al., "Microfacet Models for Refraction through Rough Surfaces", 2007).
+///
+/// The roughness (see `Ggx::from_roughness`) is the mean of the color channels of the texture.
 #[derive(Debug, Clone)]
-pub struct DiffuseLight {
+pub struct RoughDielectric {
+    ref_idx: Float,
+    roughness: Texture,
+}
+
+impl RoughDielectric {
+    pub fn new(ref_idx: Float, roughness: Texture) -> RoughDielectric {
+        RoughDielectric { ref_idx, roughness }
+    }
+}
+
+impl MaterialTrait for RoughDielectric {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        let (normal, eta) = if ray.direction().dot(&hit_record.normal) > 0. {
+            (-hit_record.normal, 1. / self.ref_idx)
+        } else {
+            (hit_record.normal, self.ref_idx)
+        };
+        let frame = Onb::from_w_and_tangent(&normal, &hit_record.dpdu);
+        let wo = frame.to_local(&-ray.direction().unit_vector());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        let roughness = self
+            .roughness
+            .sample(hit_record.u, hit_record.v, &hit_record.p);
+        let roughness = (roughness.r() + roughness.g() + roughness.b()) / 3.;
+        let distribution = Ggx::from_roughness(roughness, roughness);
+
+        let (wi, _) = microfacet::sample_dielectric(&distribution, &wo, eta, rng)?;
+        let weight = distribution.g2(&wo, &wi) / distribution.g1(&wo);
+        Some((
+            ray.spawn(hit_record.p, frame.to_world(&wi)),
+            Vec3::new(weight, weight, weight),
+        ))
+    }
+}
+
+/// Light emitted by a surface: the color of a texture times an intensity, optionally scaled by a
+/// spectral power distribution. Two-sided unless `one_sided` is used.
+#[derive(Debug, Clone)]
+pub struct Emission {
     texture: Texture,
+    intensity: Float,
+    two_sided: bool,
+    spd: Option<Spd>,
+    /// The color of `spd` for paths without a wavelength.
+    spd_rgb: Vec3,
+}
+
+impl Emission {
+    pub fn new(texture: Texture) -> Emission {
+        Emission {
+            texture,
+            intensity: 1.,
+            two_sided: true,
+            spd: None,
+            spd_rgb: Vec3::new(1., 1., 1.),
+        }
+    }
+
+    /// Scales the emission so the texture only has to give the color.
+    pub fn with_intensity(mut self, intensity: Float) -> Emission {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Only emits to the side the normal points to.
+    pub fn one_sided(mut self) -> Emission {
+        self.two_sided = false;
+        self
+    }
+
+    /// Scales the emission of the texture by a spectral power distribution.
+    pub fn with_spd(mut self, spd: Spd) -> Emission {
+        self.spd_rgb = spd.to_rgb();
+        self.spd = Some(spd);
+        self
+    }
+
+    fn radiance(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> Vec3 {
+        if !self.two_sided && ray.direction().dot(&hit_record.normal) >= 0. {
+            return Vec3::zero();
+        }
+        let wavelength = ray.wavelength();
+        let color = self
+            .texture
+            .sample(hit_record.u, hit_record.v, &hit_record.p);
+        let color = self.intensity * spectrum::at_wavelength(&color, wavelength);
+        match (wavelength, &self.spd) {
+            (Some(lambda), Some(spd)) => spd.at(lambda) * color,
+            (None, Some(_)) => self.spd_rgb * color,
+            (_, None) => color,
+        }
+    }
+}
+
+/// A light which doesn't scatter.
+#[derive(Debug, Clone)]
+pub struct DiffuseLight {
+    emission: Emission,
 }
 
 impl DiffuseLight {
     pub fn new(texture: Texture) -> DiffuseLight {
-        DiffuseLight { texture }
+        DiffuseLight::from_emission(Emission::new(texture))
+    }
+
+    pub fn from_emission(emission: Emission) -> DiffuseLight {
+        DiffuseLight { emission }
     }
 }
 
@@ -214,8 +719,49 @@ impl MaterialTrait for DiffuseLight {
         None
     }
 
-    fn emit(&self, u: Float, v: Float, p: &Vec3) -> Vec3 {
-        self.texture.sample(u, v, p)
+    fn emit(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> Vec3 {
+        self.emission.radiance(ray, hit_record)
+    }
+}
+
+/// Adds emission to any material (e.g. glowing glass) which still scatters like `base`.
+#[derive(Debug, Clone)]
+pub struct Emissive {
+    base: Arc<Material>,
+    emission: Emission,
+}
+
+impl Emissive {
+    pub fn new(base: Arc<Material>, emission: Emission) -> Emissive {
+        Emissive { base, emission }
+    }
+}
+
+impl MaterialTrait for Emissive {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        self.base.scatter(ray, hit_record, rng)
+    }
+
+    fn emit(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> Vec3 {
+        self.base.emit(ray, hit_record) + self.emission.radiance(ray, hit_record)
+    }
+
+    fn is_walk_step(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> bool {
+        self.base.is_walk_step(ray, hit_record)
+    }
+
+    fn evaluate(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        direction: &Vec3,
+    ) -> Option<(Vec3, Float)> {
+        self.base.evaluate(ray, hit_record, direction)
     }
 }
 
@@ -238,17 +784,235 @@ impl MaterialTrait for Isotropic {
         rng: &mut R,
     ) -> Option<(Ray, Vec3)> {
         Some((
-            Ray::new(hit_record.p, random_in_unit_sphere(rng), ray.time()),
+            ray.spawn(hit_record.p, random_in_unit_sphere(rng)),
             self.albedo
                 .sample(hit_record.u, hit_record.v, &hit_record.p),
         ))
     }
 }
 
+/// Picks `first` or `second` at each scattering, `second` with the probability given by the mean
+/// of the color channels of `weight` (e.g. rust patches from a noise texture over a metal).
+#[derive(Debug, Clone)]
+pub struct Mix {
+    first: Arc<Material>,
+    second: Arc<Material>,
+    weight: Texture,
+}
+
+impl Mix {
+    pub fn new(first: Arc<Material>, second: Arc<Material>, weight: Texture) -> Mix {
+        Mix {
+            first,
+            second,
+            weight,
+        }
+    }
+
+    fn weight(&self, u: Float, v: Float, p: &Vec3) -> Float {
+        let weight = self.weight.sample(u, v, p);
+        clamp((weight.r() + weight.g() + weight.b()) / 3., 0., 1.)
+    }
+}
+
+impl MaterialTrait for Mix {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        let weight = self.weight(hit_record.u, hit_record.v, &hit_record.p);
+        if rng.gen::<Float>() < weight {
+            self.second.scatter(ray, hit_record, rng)
+        } else {
+            self.first.scatter(ray, hit_record, rng)
+        }
+    }
+
+    fn emit(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> Vec3 {
+        let weight = self.weight(hit_record.u, hit_record.v, &hit_record.p);
+        (1. - weight) * self.first.emit(ray, hit_record)
+            + weight * self.second.emit(ray, hit_record)
+    }
+}
+
+/// A clear dielectric layer (e.g. varnish) over a `base` material.
+///
+/// Light is reflected by the (rough) coating with the Fresnel reflectance and otherwise refracted
+/// to the base. What the base reflects back is attenuated by the transmission through the coating
+/// so the layers never reflect more than the base alone would. Light which is reflected back to
+/// the base inside of the coating is neglected.
+#[derive(Debug, Clone)]
+pub struct Coated {
+    base: Arc<Material>,
+    ref_idx: Float,
+    distribution: Ggx,
+}
+
+impl Coated {
+    /// A smooth coating.
+    pub fn new(base: Arc<Material>, ref_idx: Float) -> Coated {
+        Coated {
+            base,
+            ref_idx,
+            distribution: Ggx::from_roughness(0., 0.),
+        }
+    }
+
+    /// See `Ggx::from_roughness`.
+    pub fn with_roughness(mut self, roughness: Float) -> Coated {
+        self.distribution = Ggx::from_roughness(roughness, roughness);
+        self
+    }
+}
+
+impl MaterialTrait for Coated {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        let normal = if ray.direction().dot(&hit_record.normal) > 0. {
+            -hit_record.normal
+        } else {
+            hit_record.normal
+        };
+        let frame = Onb::from_w_and_tangent(&normal, &hit_record.dpdu);
+        let wo = frame.to_local(&-ray.direction().unit_vector());
+        if wo.z() <= 0. {
+            return None;
+        }
+
+        // Reflection is chosen with the probability of the Fresnel reflectance at the sampled
+        // microfacet which cancels it from the weight.
+        let m = self.distribution.sample_visible_normal(&wo, rng);
+        if rng.gen::<Float>() < microfacet::fresnel_dielectric(wo.dot(&m), self.ref_idx) {
+            let wi = microfacet::reflect(&wo, &m);
+            if wi.z() <= 0. {
+                return None;
+            }
+            let weight = self.distribution.g2(&wo, &wi) / self.distribution.g1(&wo);
+            return Some((
+                ray.spawn(hit_record.p, frame.to_world(&wi)),
+                Vec3::new(weight, weight, weight),
+            ));
+        }
+
+        let (scattered, attenuation) = self.base.scatter(ray, hit_record, rng)?;
+        let cos_theta = scattered.direction().unit_vector().dot(&normal);
+        let transmission = if cos_theta > 0. {
+            1. - microfacet::fresnel_dielectric(cos_theta, self.ref_idx)
+        } else {
+            // Transmitted through the base
+            1.
+        };
+        Some((scattered, transmission * attenuation))
+    }
+
+    fn emit(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> Vec3 {
+        self.base.emit(ray, hit_record)
+    }
+}
+
+/// How `Bump` perturbs the shading normal.
+#[derive(Debug, Clone)]
+pub enum BumpMap {
+    /// A tangent space normal map. The red, green and blue channels map `[0, 1]` to the
+    /// components `[-1, 1]` along `dpdu`, `dpdv` and the normal.
+    Normal(Texture),
+    /// Displaces the surface along the normal by the mean of the color channels times `scale`.
+    Height { height: Texture, scale: Float },
+}
+
+/// Adds surface detail to `base` by perturbing the normal of the hit before scattering. Requires
+/// shapes which provide `dpdu` and `dpdv`; the normal is left alone otherwise.
+#[derive(Debug, Clone)]
+pub struct Bump {
+    base: Arc<Material>,
+    map: BumpMap,
+}
+
+impl Bump {
+    pub fn normal_map(base: Arc<Material>, normals: Texture) -> Bump {
+        Bump {
+            base,
+            map: BumpMap::Normal(normals),
+        }
+    }
+
+    pub fn height_map(base: Arc<Material>, height: Texture, scale: Float) -> Bump {
+        Bump {
+            base,
+            map: BumpMap::Height { height, scale },
+        }
+    }
+
+    fn normal(&self, hit_record: &HitRecord<'_>) -> Vec3 {
+        let (u, v, p) = (hit_record.u, hit_record.v, hit_record.p);
+        let (normal, dpdu, dpdv) = (hit_record.normal, hit_record.dpdu, hit_record.dpdv);
+        if dpdu.length_squared() == 0. || dpdv.length_squared() == 0. {
+            return normal;
+        }
+        match &self.map {
+            BumpMap::Normal(normals) => {
+                let tangent = (dpdu - normal.dot(&dpdu) * normal).unit_vector();
+                let mut bitangent = normal.cross(&tangent);
+                if bitangent.dot(&dpdv) < 0. {
+                    bitangent = -bitangent;
+                }
+                let color = normals.sample(u, v, &p);
+                let local = 2. * color - Vec3::new(1., 1., 1.);
+                (local.x() * tangent + local.y() * bitangent + local.z() * normal).unit_vector()
+            }
+            BumpMap::Height { height, scale } => {
+                // Finite differences of the displaced surface (Blinn, "Simulation of Wrinkled
+                // Surfaces", 1978)
+                let delta = 0.0005;
+                let height = |u: Float, v: Float, p: &Vec3| {
+                    let value = height.sample(u, v, p);
+                    scale * (value.r() + value.g() + value.b()) / 3.
+                };
+                let h = height(u, v, &p);
+                let h_u = height(u + delta, v, &(p + delta * dpdu));
+                let h_v = height(u, v + delta, &(p + delta * dpdv));
+                let displaced_dpdu = dpdu + (h_u - h) / delta * normal;
+                let displaced_dpdv = dpdv + (h_v - h) / delta * normal;
+                let perturbed = displaced_dpdu.cross(&displaced_dpdv).unit_vector();
+                if perturbed.dot(&normal) < 0. {
+                    -perturbed
+                } else {
+                    perturbed
+                }
+            }
+        }
+    }
+}
+
+impl MaterialTrait for Bump {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        let perturbed = HitRecord {
+            normal: self.normal(hit_record),
+            ..*hit_record
+        };
+        self.base.scatter(ray, &perturbed, rng)
+    }
+
+    fn emit(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> Vec3 {
+        self.base.emit(ray, hit_record)
+    }
+}
+
 fn schlick(cosine: Float, ref_idx: Float) -> Float {
     let mut r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
     r0 = r0 * r0;
-    (r0 + (1.0 - r0) * (1.0 - cosine).powi(5))
+    r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
 }
 
 fn refract(v: &Vec3, n: &Vec3, ni_over_nt: Float) -> Option<Vec3> {
@@ -262,7 +1026,16 @@ fn refract(v: &Vec3, n: &Vec3, ni_over_nt: Float) -> Option<Vec3> {
     }
 }
 
-fn random_in_unit_sphere<R: Rng>(rng: &mut R) -> Vec3 {
+/// A direction in the hemisphere around z with a density proportional to the cosine to z.
+pub(crate) fn random_cosine_direction<R: Rng>(rng: &mut R) -> Vec3 {
+    let r1 = rng.gen::<Float>();
+    let r2 = rng.gen::<Float>();
+    let phi = 2. * PI * r1;
+    let r = r2.sqrt();
+    Vec3::new(r * phi.cos(), r * phi.sin(), (1. - r2).sqrt())
+}
+
+pub(crate) fn random_in_unit_sphere<R: Rng>(rng: &mut R) -> Vec3 {
     loop {
         let p = Vec3::new(rng.gen(), rng.gen(), rng.gen()) * 2.0 - Vec3::new(1., 1., 1.);
         if p.length_squared() < 1.0 {
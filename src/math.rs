@@ -1,13 +1,23 @@
 #[cfg(not(feature = "f64"))]
 pub mod float {
     pub type Float = f32;
-    pub use std::f32::*;
+    pub use std::f32::consts;
+
+    pub const MIN: Float = f32::MIN;
+    pub const MAX: Float = f32::MAX;
+    pub const EPSILON: Float = f32::EPSILON;
+    pub const INFINITY: Float = f32::INFINITY;
 }
 
 #[cfg(feature = "f64")]
 pub mod float {
     pub type Float = f64;
-    pub use std::f64::*;
+    pub use std::f64::consts;
+
+    pub const MIN: Float = f64::MIN;
+    pub const MAX: Float = f64::MAX;
+    pub const EPSILON: Float = f64::EPSILON;
+    pub const INFINITY: Float = f64::INFINITY;
 }
 
 pub fn partial_min<T: PartialOrd>(a: T, b: T) -> T {
@@ -29,3 +39,121 @@ pub fn partial_max<T: PartialOrd>(a: T, b: T) -> T {
 pub fn clamp<T: PartialOrd>(value: T, low: T, high: T) -> T {
     partial_min(partial_max(value, low), high)
 }
+
+/// The ray parameter just past a hit at `t` from which to continue searching for further hits.
+/// The offset grows with `t` since a fixed one is lost in the rounding of large parameters.
+pub fn continue_past(t: float::Float) -> float::Float {
+    t + partial_max(0.0001, t.abs() * 256. * float::EPSILON)
+}
+
+const POLYNOMIAL_EPSILON: f64 = 1e-9;
+
+/// Real roots of `c[0] + c[1] x + c[2] x^2`.
+pub fn solve_quadratic(c: [f64; 3]) -> Vec<f64> {
+    let p = c[1] / (2. * c[2]);
+    let q = c[0] / c[2];
+    let discriminant = p * p - q;
+    if discriminant.abs() < POLYNOMIAL_EPSILON {
+        vec![-p]
+    } else if discriminant < 0. {
+        vec![]
+    } else {
+        let sqrt_discriminant = discriminant.sqrt();
+        vec![sqrt_discriminant - p, -sqrt_discriminant - p]
+    }
+}
+
+/// Real roots of `c[0] + c[1] x + c[2] x^2 + c[3] x^3` (Cardano's method).
+pub fn solve_cubic(c: [f64; 4]) -> Vec<f64> {
+    use std::f64::consts::PI;
+
+    // Normal form x^3 + Ax^2 + Bx + C and substitution x = y - A/3 gives y^3 + 3py + 2q
+    let a = c[2] / c[3];
+    let b = c[1] / c[3];
+    let c = c[0] / c[3];
+    let a_squared = a * a;
+    let p = (-a_squared / 3. + b) / 3.;
+    let q = (2. / 27. * a * a_squared - a * b / 3. + c) / 2.;
+    let p_cubed = p * p * p;
+    let discriminant = q * q + p_cubed;
+
+    let roots = if discriminant.abs() < POLYNOMIAL_EPSILON {
+        if q.abs() < POLYNOMIAL_EPSILON {
+            vec![0.]
+        } else {
+            let u = (-q).cbrt();
+            vec![2. * u, -u]
+        }
+    } else if discriminant < 0. {
+        let phi = clamp(-q / (-p_cubed).sqrt(), -1., 1.).acos() / 3.;
+        let t = 2. * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + PI / 3.).cos(),
+            -t * (phi - PI / 3.).cos(),
+        ]
+    } else {
+        let sqrt_discriminant = discriminant.sqrt();
+        vec![(sqrt_discriminant - q).cbrt() - (sqrt_discriminant + q).cbrt()]
+    };
+
+    roots.into_iter().map(|y| y - a / 3.).collect()
+}
+
+/// Real roots of `c[0] + c[1] x + c[2] x^2 + c[3] x^3 + c[4] x^4` (Ferrari's method). The roots
+/// are refined with a few Newton iterations.
+pub fn solve_quartic(c: [f64; 5]) -> Vec<f64> {
+    // Normal form x^4 + Ax^3 + Bx^2 + Cx + D and substitution x = y - A/4 gives
+    // y^4 + py^2 + qy + r
+    let a = c[3] / c[4];
+    let b = c[2] / c[4];
+    let cc = c[1] / c[4];
+    let d = c[0] / c[4];
+    let a_squared = a * a;
+    let p = -3. / 8. * a_squared + b;
+    let q = a_squared * a / 8. - a * b / 2. + cc;
+    let r = -3. / 256. * a_squared * a_squared + a_squared * b / 16. - a * cc / 4. + d;
+
+    let roots = if r.abs() < POLYNOMIAL_EPSILON {
+        let mut roots = solve_cubic([q, p, 0., 1.]);
+        roots.push(0.);
+        roots
+    } else {
+        // Take one real root of the resolvent cubic to build two quadratic equations
+        let z = solve_cubic([r * p / 2. - q * q / 8., -r, -p / 2., 1.])[0];
+        let u = z * z - r;
+        let v = 2. * z - p;
+        let u = if u.abs() < POLYNOMIAL_EPSILON {
+            0.
+        } else if u > 0. {
+            u.sqrt()
+        } else {
+            return vec![];
+        };
+        let v = if v.abs() < POLYNOMIAL_EPSILON {
+            0.
+        } else if v > 0. {
+            v.sqrt()
+        } else {
+            return vec![];
+        };
+        let mut roots = solve_quadratic([z - u, if q < 0. { -v } else { v }, 1.]);
+        roots.extend(solve_quadratic([z + u, if q < 0. { v } else { -v }, 1.]));
+        roots
+    };
+
+    roots
+        .into_iter()
+        .map(|y| {
+            let mut x = y - a / 4.;
+            for _ in 0..2 {
+                let f = (((c[4] * x + c[3]) * x + c[2]) * x + c[1]) * x + c[0];
+                let df = ((4. * c[4] * x + 3. * c[3]) * x + 2. * c[2]) * x + c[1];
+                if df.abs() > POLYNOMIAL_EPSILON {
+                    x -= f / df;
+                }
+            }
+            x
+        })
+        .collect()
+}
@@ -0,0 +1,248 @@
+use std::cmp::Ordering;
+use std::ops::Mul;
+
+use crate::bvh::Aabb;
+use crate::math::float::{self, Float};
+use crate::math::partial_max;
+use crate::vec3::Vec3;
+
+/// A row-major 4x4 matrix operating on column vectors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    m: [[Float; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[Float; 4]; 4]) -> Matrix4 {
+        Matrix4 { m }
+    }
+
+    pub fn identity() -> Matrix4 {
+        Matrix4::new([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn translation(offset: Vec3) -> Matrix4 {
+        Matrix4::new([
+            [1., 0., 0., offset.x()],
+            [0., 1., 0., offset.y()],
+            [0., 0., 1., offset.z()],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn scaling(factor: Vec3) -> Matrix4 {
+        Matrix4::new([
+            [factor.x(), 0., 0., 0.],
+            [0., factor.y(), 0., 0.],
+            [0., 0., factor.z(), 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// A counterclockwise rotation by `angle` degrees around `axis` (which doesn't need to be
+    /// normalized).
+    pub fn rotation(axis: Vec3, angle: Float) -> Matrix4 {
+        let a = axis.unit_vector();
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let c = 1. - cos;
+        Matrix4::new([
+            [
+                a.x() * a.x() * c + cos,
+                a.x() * a.y() * c - a.z() * sin,
+                a.x() * a.z() * c + a.y() * sin,
+                0.,
+            ],
+            [
+                a.y() * a.x() * c + a.z() * sin,
+                a.y() * a.y() * c + cos,
+                a.y() * a.z() * c - a.x() * sin,
+                0.,
+            ],
+            [
+                a.z() * a.x() * c - a.y() * sin,
+                a.z() * a.y() * c + a.x() * sin,
+                a.z() * a.z() * c + cos,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn rotation_x(angle: Float) -> Matrix4 {
+        Matrix4::rotation(Vec3::new(1., 0., 0.), angle)
+    }
+
+    pub fn rotation_y(angle: Float) -> Matrix4 {
+        Matrix4::rotation(Vec3::new(0., 1., 0.), angle)
+    }
+
+    pub fn rotation_z(angle: Float) -> Matrix4 {
+        Matrix4::rotation(Vec3::new(0., 0., 1.), angle)
+    }
+
+    pub fn get(&self, row: usize, column: usize) -> Float {
+        self.m[row][column]
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Matrix4::new(m)
+    }
+
+    /// Computes the inverse using Gauss-Jordan elimination with partial pivoting. Returns `None`
+    /// if the matrix is singular or has an entry which isn't finite.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        if self.m.iter().flatten().any(|value| !value.is_finite()) {
+            return None;
+        }
+        let mut a = self.m;
+        let mut inv = Matrix4::identity().m;
+        for column in 0..4 {
+            // Row operations keep the scale of each column so a pivot is compared to the largest
+            // entry of its column in the original matrix (a small uniform scale is fine).
+            let column_scale = self
+                .m
+                .iter()
+                .map(|row| row[column].abs())
+                .fold(0., partial_max);
+            let pivot = (column..4)
+                .max_by(|&i, &j| {
+                    a[i][column]
+                        .abs()
+                        .partial_cmp(&a[j][column].abs())
+                        .unwrap_or(Ordering::Equal)
+                })
+                .unwrap();
+            if a[pivot][column].abs() <= 16. * float::EPSILON * column_scale {
+                return None;
+            }
+            a.swap(column, pivot);
+            inv.swap(column, pivot);
+
+            let scale = 1. / a[column][column];
+            for j in 0..4 {
+                a[column][j] *= scale;
+                inv[column][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != column {
+                    let factor = a[row][column];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[column][j];
+                        inv[row][j] -= factor * inv[column][j];
+                    }
+                }
+            }
+        }
+        Some(Matrix4::new(inv))
+    }
+
+    /// Transforms a point (w = 1).
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x() + m[0][1] * p.y() + m[0][2] * p.z() + m[0][3];
+        let y = m[1][0] * p.x() + m[1][1] * p.y() + m[1][2] * p.z() + m[1][3];
+        let z = m[2][0] * p.x() + m[2][1] * p.y() + m[2][2] * p.z() + m[2][3];
+        let w = m[3][0] * p.x() + m[3][1] * p.y() + m[3][2] * p.z() + m[3][3];
+        if w == 1. {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x, y, z) / w
+        }
+    }
+
+    /// Transforms a direction (w = 0), ignoring the translation.
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Matrix4::new(m)
+    }
+}
+
+/// An invertible affine transformation together with its inverse and inverse transpose (for
+/// transforming normals).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AffineTransform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+    inverse_transpose: Matrix4,
+}
+
+impl AffineTransform {
+    /// Returns `None` if `matrix` isn't invertible.
+    pub fn new(matrix: Matrix4) -> Option<AffineTransform> {
+        matrix.inverse().map(|inverse| AffineTransform {
+            matrix,
+            inverse,
+            inverse_transpose: inverse.transpose(),
+        })
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> &Matrix4 {
+        &self.inverse
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Transforms a surface normal. The result is normalized.
+    pub fn transform_normal(&self, n: &Vec3) -> Vec3 {
+        self.inverse_transpose.transform_vector(n).unit_vector()
+    }
+
+    pub fn inverse_transform_point(&self, p: &Vec3) -> Vec3 {
+        self.inverse.transform_point(p)
+    }
+
+    pub fn inverse_transform_vector(&self, v: &Vec3) -> Vec3 {
+        self.inverse.transform_vector(v)
+    }
+
+    /// The smallest AABB containing the transformed corners of `aabb`.
+    pub fn transform_aabb(&self, aabb: &Aabb) -> Aabb {
+        transform_aabb(&self.matrix, aabb)
+    }
+}
+
+/// The smallest AABB containing the corners of `aabb` transformed by `matrix`.
+pub fn transform_aabb(matrix: &Matrix4, aabb: &Aabb) -> Aabb {
+    aabb.corners()
+        .iter()
+        .map(|corner| matrix.transform_point(corner))
+        .fold(Aabb::empty(), |result, p| result.union(&Aabb::new(p, p)))
+}
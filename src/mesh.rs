@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::hit::Hit;
+use crate::material::Material;
+use crate::math::float::Float;
+use crate::shape::Triangle;
+use crate::texture::{Sample, Texture};
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// An indexed triangle mesh which can be refined before it's turned into `Triangle`s for the
+/// `Bvh`. Faces are counterclockwise around their normal.
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    positions: Vec<Vec3>,
+    uvs: Vec<(Float, Float)>,
+    faces: Vec<[usize; 3]>,
+}
+
+/// The vertices opposite of an edge in the faces next to it (one for boundary edges, two for
+/// interior edges of a manifold mesh).
+struct Edge {
+    opposite: Vec<usize>,
+}
+
+impl TriangleMesh {
+    /// Panics if a face references a vertex which doesn't exist. All texture coordinates are
+    /// (0, 0).
+    pub fn new(positions: Vec<Vec3>, faces: Vec<[usize; 3]>) -> TriangleMesh {
+        assert!(faces.iter().flatten().all(|&i| i < positions.len()));
+        TriangleMesh {
+            uvs: vec![(0., 0.); positions.len()],
+            positions,
+            faces,
+        }
+    }
+
+    /// Sets one texture coordinate per vertex. Panics if the number doesn't match.
+    pub fn with_uvs(mut self, uvs: Vec<(Float, Float)>) -> TriangleMesh {
+        assert_eq!(uvs.len(), self.positions.len());
+        self.uvs = uvs;
+        self
+    }
+
+    pub fn positions(&self) -> &[Vec3] {
+        &self.positions
+    }
+
+    pub fn faces(&self) -> &[[usize; 3]] {
+        &self.faces
+    }
+
+    fn edges(&self) -> HashMap<(usize, usize), Edge> {
+        let mut edges: HashMap<(usize, usize), Edge> = HashMap::new();
+        for &[a, b, c] in &self.faces {
+            for &(v0, v1, opposite) in &[(a, b, c), (b, c, a), (c, a, b)] {
+                edges
+                    .entry((v0.min(v1), v0.max(v1)))
+                    .or_insert_with(|| Edge {
+                        opposite: Vec::new(),
+                    })
+                    .opposite
+                    .push(opposite);
+            }
+        }
+        edges
+    }
+
+    /// Applies `levels` steps of Loop subdivision. Each step splits every triangle into four and
+    /// smooths the vertices, so the mesh converges to a smooth surface. Edges with only one face
+    /// are treated as boundary (and so are non-manifold edges) and are smoothed as a curve.
+    pub fn subdivide(&self, levels: usize) -> TriangleMesh {
+        (0..levels).fold(self.clone(), |mesh, _| mesh.subdivide_once())
+    }
+
+    fn subdivide_once(&self) -> TriangleMesh {
+        let edges = self.edges();
+        let vertex_count = self.positions.len();
+
+        // New positions of the existing (even) vertices
+        let mut neighbors = vec![Vec::new(); vertex_count];
+        let mut boundary_neighbors = vec![Vec::new(); vertex_count];
+        for (&(a, b), edge) in &edges {
+            neighbors[a].push(b);
+            neighbors[b].push(a);
+            if edge.opposite.len() != 2 {
+                boundary_neighbors[a].push(b);
+                boundary_neighbors[b].push(a);
+            }
+        }
+        let mut positions: Vec<Vec3> = (0..vertex_count)
+            .map(|i| {
+                let p = self.positions[i];
+                if !boundary_neighbors[i].is_empty() {
+                    if boundary_neighbors[i].len() != 2 {
+                        // A corner; keep it in place.
+                        return p;
+                    }
+                    let [b0, b1] = [boundary_neighbors[i][0], boundary_neighbors[i][1]];
+                    return 0.75 * p + 0.125 * (self.positions[b0] + self.positions[b1]);
+                }
+                let n = neighbors[i].len();
+                let beta = if n == 3 {
+                    3. / 16.
+                } else {
+                    3. / (8. * n as Float)
+                };
+                let sum = neighbors[i]
+                    .iter()
+                    .fold(Vec3::zero(), |sum, &j| sum + self.positions[j]);
+                (1. - n as Float * beta) * p + beta * sum
+            })
+            .collect();
+        let mut uvs = self.uvs.clone();
+
+        // New (odd) vertices on the edges
+        let mut edge_vertices = HashMap::with_capacity(edges.len());
+        for (&(a, b), edge) in &edges {
+            let (pa, pb) = (self.positions[a], self.positions[b]);
+            let position = if let [c, d] = edge.opposite[..] {
+                0.375 * (pa + pb) + 0.125 * (self.positions[c] + self.positions[d])
+            } else {
+                0.5 * (pa + pb)
+            };
+            edge_vertices.insert((a, b), positions.len());
+            positions.push(position);
+            let (ua, ub) = (self.uvs[a], self.uvs[b]);
+            uvs.push(((ua.0 + ub.0) / 2., (ua.1 + ub.1) / 2.));
+        }
+
+        let edge_vertex = |v0: usize, v1: usize| edge_vertices[&(v0.min(v1), v0.max(v1))];
+        let faces = self
+            .faces
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (edge_vertex(a, b), edge_vertex(b, c), edge_vertex(c, a));
+                vec![[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]
+            })
+            .collect();
+
+        TriangleMesh {
+            positions,
+            uvs,
+            faces,
+        }
+    }
+
+    /// Area weighted averages of the normals of the faces around each vertex.
+    pub fn vertex_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![Vec3::zero(); self.positions.len()];
+        for &[a, b, c] in &self.faces {
+            let (pa, pb, pc) = (self.positions[a], self.positions[b], self.positions[c]);
+            // The length of the cross product is twice the area.
+            let normal = (pb - pa).cross(&(pc - pa));
+            normals[a] += normal;
+            normals[b] += normal;
+            normals[c] += normal;
+        }
+        normals
+            .into_iter()
+            .map(|n| {
+                if n.length_squared() > 0. {
+                    n.unit_vector()
+                } else {
+                    n
+                }
+            })
+            .collect()
+    }
+
+    /// Moves every vertex along its normal by `scale` times the value of `texture` (the mean of
+    /// the color channels) at the texture coordinates and position of the vertex. Subdivide first
+    /// so the mesh is fine enough to show the details of the texture.
+    pub fn displace(&self, texture: &Texture, scale: Float) -> TriangleMesh {
+        let normals = self.vertex_normals();
+        let positions = self
+            .positions
+            .iter()
+            .zip(normals.iter())
+            .zip(self.uvs.iter())
+            .map(|((p, n), &(u, v))| {
+                let color = texture.sample(u, v, p);
+                let height = (color.r() + color.g() + color.b()) / 3.;
+                *p + scale * height * *n
+            })
+            .collect();
+        TriangleMesh {
+            positions,
+            uvs: self.uvs.clone(),
+            faces: self.faces.clone(),
+        }
+    }
+
+    /// Smooth shaded triangles ready for `Bvh::new`.
+    pub fn triangles<R: Rng>(&self, material: Arc<Material>) -> Vec<Box<dyn Hit<R>>> {
+        let normals = self.vertex_normals();
+        self.faces
+            .iter()
+            .map(|&[a, b, c]| {
+                Box::new(
+                    Triangle::new(
+                        self.positions[a],
+                        self.positions[b],
+                        self.positions[c],
+                        Arc::clone(&material),
+                    )
+                    .with_uvs([self.uvs[a], self.uvs[b], self.uvs[c]])
+                    .with_normals([normals[a], normals[b], normals[c]]),
+                ) as Box<dyn Hit<R>>
+            })
+            .collect()
+    }
+}
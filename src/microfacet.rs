@@ -0,0 +1,245 @@
+use crate::math::float::consts::PI;
+use crate::math::float::Float;
+use crate::math::{clamp, partial_max};
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// The smallest alpha; perfectly smooth surfaces are approximated by very sharp distributions.
+const MIN_ALPHA: Float = 1e-4;
+
+/// The anisotropic GGX (Trowbridge-Reitz) distribution of microfacet normals with the Smith
+/// masking function.
+///
+/// All directions are given in a local frame whose z axis is the macro surface normal and whose
+/// x axis is the direction of `alpha_x` (the tangent).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ggx {
+    alpha_x: Float,
+    alpha_y: Float,
+}
+
+impl Ggx {
+    pub fn new(alpha_x: Float, alpha_y: Float) -> Ggx {
+        Ggx {
+            alpha_x: partial_max(alpha_x, MIN_ALPHA),
+            alpha_y: partial_max(alpha_y, MIN_ALPHA),
+        }
+    }
+
+    /// Maps the perceptually more linear roughness in `[0, 1]` to alpha (`alpha = roughness²`).
+    pub fn from_roughness(roughness_x: Float, roughness_y: Float) -> Ggx {
+        Ggx::new(roughness_x * roughness_x, roughness_y * roughness_y)
+    }
+
+    pub fn alpha_x(&self) -> Float {
+        self.alpha_x
+    }
+
+    pub fn alpha_y(&self) -> Float {
+        self.alpha_y
+    }
+
+    /// The density of microfacets with the normal `m`.
+    pub fn d(&self, m: &Vec3) -> Float {
+        if m.z() <= 0. {
+            return 0.;
+        }
+        let x = m.x() / self.alpha_x;
+        let y = m.y() / self.alpha_y;
+        let denominator = x * x + y * y + m.z() * m.z();
+        1. / (PI * self.alpha_x * self.alpha_y * denominator * denominator)
+    }
+
+    fn lambda(&self, w: &Vec3) -> Float {
+        let x = self.alpha_x * w.x();
+        let y = self.alpha_y * w.y();
+        let tan2 = (x * x + y * y) / (w.z() * w.z());
+        ((1. + tan2).sqrt() - 1.) / 2.
+    }
+
+    /// The fraction of microfacets visible from `w`.
+    pub fn g1(&self, w: &Vec3) -> Float {
+        if w.z() == 0. {
+            return 0.;
+        }
+        1. / (1. + self.lambda(w))
+    }
+
+    /// The height-correlated fraction of microfacets visible from both directions.
+    pub fn g2(&self, wo: &Vec3, wi: &Vec3) -> Float {
+        if wo.z() == 0. || wi.z() == 0. {
+            return 0.;
+        }
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// Samples a microfacet normal proportional to its visible area from `wo` (which has to be
+    /// above the surface) with the method of Heitz, "Sampling the GGX Distribution of Visible
+    /// Normals" (2018).
+    pub fn sample_visible_normal<R: Rng>(&self, wo: &Vec3, rng: &mut R) -> Vec3 {
+        // Stretch the view direction so the distribution becomes a hemisphere.
+        let vh = Vec3::new(self.alpha_x * wo.x(), self.alpha_y * wo.y(), wo.z()).unit_vector();
+        let length_squared = vh.x() * vh.x() + vh.y() * vh.y();
+        let t1 = if length_squared > 0. {
+            Vec3::new(-vh.y(), vh.x(), 0.) / length_squared.sqrt()
+        } else {
+            Vec3::new(1., 0., 0.)
+        };
+        let t2 = vh.cross(&t1);
+
+        // Sample the projected area of the hemisphere.
+        let r = rng.gen::<Float>().sqrt();
+        let phi = 2. * PI * rng.gen::<Float>();
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z());
+        let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * vh;
+
+        Vec3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(0.)).unit_vector()
+    }
+
+    /// The density of `sample_visible_normal` returning `m`.
+    pub fn pdf_visible_normal(&self, wo: &Vec3, m: &Vec3) -> Float {
+        if wo.z() <= 0. {
+            return 0.;
+        }
+        self.g1(wo) * partial_max(wo.dot(m), 0.) * self.d(m) / wo.z()
+    }
+}
+
+/// The index of refraction `eta` and absorption coefficient `k` of a conductor for red, green
+/// and blue light.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComplexIor {
+    pub eta: Vec3,
+    pub k: Vec3,
+}
+
+impl ComplexIor {
+    pub fn new(eta: Vec3, k: Vec3) -> ComplexIor {
+        ComplexIor { eta, k }
+    }
+
+    pub fn gold() -> ComplexIor {
+        ComplexIor::new(
+            Vec3::new(0.143, 0.374, 1.442),
+            Vec3::new(3.983, 2.385, 1.603),
+        )
+    }
+
+    pub fn copper() -> ComplexIor {
+        ComplexIor::new(
+            Vec3::new(0.200, 0.924, 1.102),
+            Vec3::new(3.912, 2.452, 2.142),
+        )
+    }
+
+    pub fn aluminum() -> ComplexIor {
+        ComplexIor::new(
+            Vec3::new(1.657, 0.880, 0.521),
+            Vec3::new(9.224, 6.270, 4.837),
+        )
+    }
+
+    pub fn silver() -> ComplexIor {
+        ComplexIor::new(
+            Vec3::new(0.155, 0.117, 0.138),
+            Vec3::new(4.828, 3.122, 2.147),
+        )
+    }
+}
+
+/// The Fresnel reflectance of unpolarized light at the boundary between air and a conductor.
+pub fn fresnel_conductor(cos_theta: Float, ior: &ComplexIor) -> Vec3 {
+    let channel = |eta: Float, k: Float| {
+        let cos2 = clamp(cos_theta, 0., 1.).powi(2);
+        let sin2 = 1. - cos2;
+        let eta2 = eta * eta;
+        let k2 = k * k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4. * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+        let t2 = 2. * cos_theta * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rp + rs)
+    };
+    Vec3::new(
+        channel(ior.eta.r(), ior.k.r()),
+        channel(ior.eta.g(), ior.k.g()),
+        channel(ior.eta.b(), ior.k.b()),
+    )
+}
+
+/// The Fresnel reflectance of unpolarized light at the boundary between two dielectrics. `eta`
+/// is the index of refraction of the other side relative to the side of the incoming light.
+pub fn fresnel_dielectric(cos_theta_i: Float, eta: Float) -> Float {
+    let cos_theta_i = clamp(cos_theta_i, 0., 1.);
+    let sin_theta_t = (1. - cos_theta_i * cos_theta_i).max(0.).sqrt() / eta;
+    if sin_theta_t >= 1. {
+        return 1.;
+    }
+    let cos_theta_t = (1. - sin_theta_t * sin_theta_t).max(0.).sqrt();
+    let parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.
+}
+
+/// Schlick's approximation of the Fresnel reflectance with the reflectance `f0` at normal
+/// incidence.
+pub fn fresnel_schlick(cos_theta: Float, f0: Vec3) -> Vec3 {
+    let weight = (1. - clamp(cos_theta, 0., 1.)).powi(5);
+    f0 + weight * (Vec3::new(1., 1., 1.) - f0)
+}
+
+/// Samples a reflected or refracted direction at a rough dielectric boundary. `eta` is the
+/// relative index of refraction as for `fresnel_dielectric`. Returns the direction and whether
+/// it's refracted.
+///
+/// Reflection is chosen with the probability of the Fresnel reflectance which cancels it from
+/// the weight (BSDF times cosine over density) of both cases. The weight then is
+/// `distribution.g2(wo, wi) / distribution.g1(wo)`.
+pub fn sample_dielectric<R: Rng>(
+    distribution: &Ggx,
+    wo: &Vec3,
+    eta: Float,
+    rng: &mut R,
+) -> Option<(Vec3, bool)> {
+    let m = distribution.sample_visible_normal(wo, rng);
+    if rng.gen::<Float>() < fresnel_dielectric(wo.dot(&m), eta) {
+        let wi = reflect(wo, &m);
+        if wi.z() <= 0. {
+            return None;
+        }
+        Some((wi, false))
+    } else {
+        let wi = refract(wo, &m, eta)?;
+        if wi.z() >= 0. {
+            return None;
+        }
+        Some((wi, true))
+    }
+}
+
+/// Mirrors `w` at `m`.
+pub fn reflect(w: &Vec3, m: &Vec3) -> Vec3 {
+    2. * w.dot(m) * *m - *w
+}
+
+/// Refracts `w` (pointing away from the boundary on the side of `m`) through the boundary with
+/// the normal `m`. `eta` is the relative index of refraction as for `fresnel_dielectric`. Returns
+/// `None` for total internal reflection.
+pub fn refract(w: &Vec3, m: &Vec3, eta: Float) -> Option<Vec3> {
+    let cos_theta_i = w.dot(m);
+    let sin2_theta_t = (1. - cos_theta_i * cos_theta_i).max(0.) / (eta * eta);
+    if sin2_theta_t >= 1. {
+        return None;
+    }
+    let cos_theta_t = (1. - sin2_theta_t).sqrt();
+    Some(-*w / eta + (cos_theta_i / eta - cos_theta_t) * *m)
+}
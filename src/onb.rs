@@ -0,0 +1,59 @@
+use crate::vec3::Vec3;
+
+/// An orthonormal basis. `w` usually is the surface normal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    /// Builds a basis around the unit vector `w` (Duff et al., "Building an Orthonormal Basis,
+    /// Revisited").
+    pub fn from_w(w: &Vec3) -> Onb {
+        let sign = if w.z() >= 0. { 1. } else { -1. };
+        let a = -1. / (sign + w.z());
+        let b = w.x() * w.y() * a;
+        Onb {
+            u: Vec3::new(1. + sign * w.x() * w.x() * a, sign * b, -sign * w.x()),
+            v: Vec3::new(b, sign + w.y() * w.y() * a, -w.y()),
+            w: *w,
+        }
+    }
+
+    /// Builds a basis around the unit vector `w` whose `u` axis is as close as possible to
+    /// `tangent`. Falls back to `from_w` if `tangent` is (nearly) parallel to `w`.
+    pub fn from_w_and_tangent(w: &Vec3, tangent: &Vec3) -> Onb {
+        let u = *tangent - tangent.dot(w) * *w;
+        if u.length_squared() < 1e-12 {
+            return Onb::from_w(w);
+        }
+        let u = u.unit_vector();
+        Onb {
+            u,
+            v: w.cross(&u),
+            w: *w,
+        }
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn w(&self) -> Vec3 {
+        self.w
+    }
+
+    pub fn to_world(&self, a: &Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+
+    pub fn to_local(&self, a: &Vec3) -> Vec3 {
+        Vec3::new(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+    }
+}
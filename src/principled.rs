@@ -0,0 +1,306 @@
+use crate::hit::HitRecord;
+use crate::material::{random_cosine_direction, MaterialTrait};
+use crate::math::float::consts::PI;
+use crate::math::float::Float;
+use crate::microfacet::{self, fresnel_schlick, Ggx};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::texture::{Sample, Texture};
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A material in the spirit of the Disney "principled" BRDF (Burley, "Physically Based Shading
+/// at Disney", 2012) extended by transmission. It mixes a diffuse base with sheen, a GGX
+/// specular lobe, a clearcoat and rough glass.
+///
+/// Every parameter is a texture; those which are a single number use the mean of the color
+/// channels. Each scattering picks one lobe with a probability proportional to an estimate of its
+/// contribution and samples it.
+#[derive(Debug, Clone)]
+pub struct Principled {
+    base_color: Texture,
+    metallic: Texture,
+    roughness: Texture,
+    specular: Texture,
+    specular_tint: Texture,
+    sheen: Texture,
+    clearcoat: Texture,
+    clearcoat_gloss: Texture,
+    transmission: Texture,
+    ior: Texture,
+}
+
+/// The parameters at a hit point.
+struct Parameters {
+    base_color: Vec3,
+    metallic: Float,
+    roughness: Float,
+    specular: Float,
+    specular_tint: Float,
+    sheen: Float,
+    clearcoat: Float,
+    clearcoat_gloss: Float,
+    transmission: Float,
+    ior: Float,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lobe {
+    Diffuse,
+    Specular,
+    Clearcoat,
+    Transmission,
+}
+
+impl Principled {
+    /// A dielectric with a roughness of 0.5, specular 0.5 (4% reflectance), a clearcoat gloss of
+    /// 1 and an index of refraction of 1.5. All other parameters are 0.
+    pub fn new(base_color: Texture) -> Principled {
+        Principled {
+            base_color,
+            metallic: Texture::scalar(0.),
+            roughness: Texture::scalar(0.5),
+            specular: Texture::scalar(0.5),
+            specular_tint: Texture::scalar(0.),
+            sheen: Texture::scalar(0.),
+            clearcoat: Texture::scalar(0.),
+            clearcoat_gloss: Texture::scalar(1.),
+            transmission: Texture::scalar(0.),
+            ior: Texture::scalar(1.5),
+        }
+    }
+
+    /// Blends from a dielectric (0) to a metal (1) whose specular color is the base color.
+    pub fn with_metallic(mut self, metallic: Texture) -> Principled {
+        self.metallic = metallic;
+        self
+    }
+
+    /// See `Ggx::from_roughness`.
+    pub fn with_roughness(mut self, roughness: Texture) -> Principled {
+        self.roughness = roughness;
+        self
+    }
+
+    /// The reflectance at normal incidence of the dielectric is `0.08 * specular`.
+    pub fn with_specular(mut self, specular: Texture) -> Principled {
+        self.specular = specular;
+        self
+    }
+
+    /// Tints the specular reflection of the dielectric towards the hue of the base color.
+    pub fn with_specular_tint(mut self, specular_tint: Texture) -> Principled {
+        self.specular_tint = specular_tint;
+        self
+    }
+
+    /// Additional grazing reflection for cloth.
+    pub fn with_sheen(mut self, sheen: Texture) -> Principled {
+        self.sheen = sheen;
+        self
+    }
+
+    /// Strength of a second, colorless specular layer.
+    pub fn with_clearcoat(mut self, clearcoat: Texture) -> Principled {
+        self.clearcoat = clearcoat;
+        self
+    }
+
+    /// Glossiness of the clearcoat from 0 (satin) to 1 (gloss).
+    pub fn with_clearcoat_gloss(mut self, clearcoat_gloss: Texture) -> Principled {
+        self.clearcoat_gloss = clearcoat_gloss;
+        self
+    }
+
+    /// Blends the dielectric from opaque (0) to rough glass (1) tinted by the base color.
+    pub fn with_transmission(mut self, transmission: Texture) -> Principled {
+        self.transmission = transmission;
+        self
+    }
+
+    /// The index of refraction for transmission.
+    pub fn with_ior(mut self, ior: Texture) -> Principled {
+        self.ior = ior;
+        self
+    }
+
+    fn parameters(&self, hit_record: &HitRecord<'_>) -> Parameters {
+        let (u, v, p) = (hit_record.u, hit_record.v, &hit_record.p);
+        let scalar = |texture: &Texture| {
+            let value = texture.sample(u, v, p);
+            (value.r() + value.g() + value.b()) / 3.
+        };
+        Parameters {
+            base_color: self.base_color.sample(u, v, p),
+            metallic: scalar(&self.metallic),
+            roughness: scalar(&self.roughness),
+            specular: scalar(&self.specular),
+            specular_tint: scalar(&self.specular_tint),
+            sheen: scalar(&self.sheen),
+            clearcoat: scalar(&self.clearcoat),
+            clearcoat_gloss: scalar(&self.clearcoat_gloss),
+            transmission: scalar(&self.transmission),
+            ior: scalar(&self.ior),
+        }
+    }
+}
+
+impl MaterialTrait for Principled {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        let parameters = self.parameters(hit_record);
+        let inside = ray.direction().dot(&hit_record.normal) > 0.;
+        let normal = if inside {
+            -hit_record.normal
+        } else {
+            hit_record.normal
+        };
+        let frame = Onb::from_w_and_tangent(&normal, &hit_record.dpdu);
+        let wo = frame.to_local(&-ray.direction().unit_vector());
+        if wo.z() <= 0. {
+            return None;
+        }
+        let white = Vec3::new(1., 1., 1.);
+        let specular_distribution = Ggx::from_roughness(parameters.roughness, parameters.roughness);
+
+        // Rays inside only reach the surface through transmission (which was tinted when
+        // entering).
+        if inside {
+            let (wi, _) = microfacet::sample_dielectric(
+                &specular_distribution,
+                &wo,
+                1. / parameters.ior,
+                rng,
+            )?;
+            let weight = specular_distribution.g2(&wo, &wi) / specular_distribution.g1(&wo);
+            return Some((
+                ray.spawn(hit_record.p, frame.to_world(&wi)),
+                Vec3::new(weight, weight, weight),
+            ));
+        }
+
+        let base_luminance = luminance(&parameters.base_color);
+        let tint = if base_luminance > 0. {
+            parameters.base_color / base_luminance
+        } else {
+            white
+        };
+        let specular_color = 0.08
+            * parameters.specular
+            * ((1. - parameters.specular_tint) * white + parameters.specular_tint * tint);
+        let f0 = (1. - parameters.metallic) * specular_color
+            + parameters.metallic * parameters.base_color;
+
+        let dielectric = 1. - parameters.metallic;
+        let diffuse_weight = dielectric * (1. - parameters.transmission);
+        let specular_weight = 1. - dielectric * parameters.transmission;
+        let clearcoat_weight = 0.25 * parameters.clearcoat;
+        let transmission_weight = dielectric * parameters.transmission;
+
+        let lobes = [
+            (
+                Lobe::Diffuse,
+                diffuse_weight * (base_luminance + parameters.sheen),
+            ),
+            (
+                Lobe::Specular,
+                specular_weight * luminance(&fresnel_schlick(wo.z(), f0)),
+            ),
+            (
+                Lobe::Clearcoat,
+                clearcoat_weight * fresnel_schlick(wo.z(), Vec3::new(0.04, 0.04, 0.04)).r(),
+            ),
+            (Lobe::Transmission, transmission_weight),
+        ];
+        let total: Float = lobes.iter().map(|l| l.1).sum();
+        if total <= 0. {
+            return None;
+        }
+        let mut u = rng.gen::<Float>() * total;
+        let (lobe, probability) = *lobes
+            .iter()
+            .find(|l| {
+                u -= l.1;
+                u < 0. && l.1 > 0.
+            })
+            .unwrap_or_else(|| lobes.iter().rev().find(|l| l.1 > 0.).unwrap());
+        let probability = probability / total;
+
+        let (wi, weight) = match lobe {
+            Lobe::Diffuse => {
+                // Burley's diffuse with retro-reflection at grazing angles and sheen. The cosine
+                // and 1 / pi cancel with the density of the cosine weighted sampling.
+                let wi = random_cosine_direction(rng);
+                let cos_d = wi.dot(&(wo + wi).unit_vector());
+                let fd90 = 0.5 + 2. * parameters.roughness * cos_d * cos_d;
+                let fl = (1. - wi.z()).powi(5);
+                let fv = (1. - wo.z()).powi(5);
+                let diffuse = (1. + (fd90 - 1.) * fl) * (1. + (fd90 - 1.) * fv);
+                let sheen = PI * parameters.sheen * (1. - cos_d).powi(5);
+                (
+                    wi,
+                    diffuse_weight * (diffuse * parameters.base_color + sheen * white),
+                )
+            }
+            Lobe::Specular => {
+                let (wi, fresnel, g) = sample_reflection(&specular_distribution, &wo, rng)?;
+                (wi, specular_weight * g * fresnel_schlick(fresnel, f0))
+            }
+            Lobe::Clearcoat => {
+                let alpha =
+                    (1. - parameters.clearcoat_gloss) * 0.1 + parameters.clearcoat_gloss * 0.001;
+                let distribution = Ggx::new(alpha, alpha);
+                let (wi, fresnel, g) = sample_reflection(&distribution, &wo, rng)?;
+                let f = fresnel_schlick(fresnel, Vec3::new(0.04, 0.04, 0.04));
+                (wi, clearcoat_weight * g * f)
+            }
+            Lobe::Transmission => {
+                let (wi, refracted) = microfacet::sample_dielectric(
+                    &specular_distribution,
+                    &wo,
+                    parameters.ior,
+                    rng,
+                )?;
+                let g = specular_distribution.g2(&wo, &wi) / specular_distribution.g1(&wo);
+                let color = if refracted {
+                    parameters.base_color
+                } else {
+                    white
+                };
+                (wi, transmission_weight * g * color)
+            }
+        };
+
+        Some((
+            ray.spawn(hit_record.p, frame.to_world(&wi)),
+            weight / probability,
+        ))
+    }
+}
+
+/// Samples a reflection off a visible microfacet. Returns the direction, the cosine for the
+/// Fresnel term and `G2 / G1`.
+fn sample_reflection<R: Rng>(
+    distribution: &Ggx,
+    wo: &Vec3,
+    rng: &mut R,
+) -> Option<(Vec3, Float, Float)> {
+    let m = distribution.sample_visible_normal(wo, rng);
+    let wi = microfacet::reflect(wo, &m);
+    if wi.z() <= 0. {
+        return None;
+    }
+    Some((
+        wi,
+        wo.dot(&m),
+        distribution.g2(wo, &wi) / distribution.g1(wo),
+    ))
+}
+
+fn luminance(color: &Vec3) -> Float {
+    0.2126 * color.r() + 0.7152 * color.g() + 0.0722 * color.b()
+}
@@ -0,0 +1,122 @@
+use std::ops::Mul;
+
+use crate::math::float::Float;
+use crate::matrix::Matrix4;
+use crate::vec3::Vec3;
+
+/// A quaternion `w + xi + yj + zk`. Only unit quaternions represent rotations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    w: Float,
+    v: Vec3,
+}
+
+impl Quaternion {
+    pub fn new(w: Float, v: Vec3) -> Quaternion {
+        Quaternion { w, v }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1., Vec3::zero())
+    }
+
+    /// A counterclockwise rotation by `angle` degrees around `axis`.
+    pub fn from_axis_angle(axis: Vec3, angle: Float) -> Quaternion {
+        let (sin, cos) = (angle.to_radians() / 2.).sin_cos();
+        Quaternion::new(cos, sin * axis.unit_vector())
+    }
+
+    pub fn w(&self) -> Float {
+        self.w
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+
+    pub fn dot(&self, other: &Quaternion) -> Float {
+        self.w * other.w + self.v.dot(&other.v)
+    }
+
+    pub fn length(&self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalized(&self) -> Quaternion {
+        let length = self.length();
+        Quaternion::new(self.w / length, self.v / length)
+    }
+
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(self.w, -self.v)
+    }
+
+    /// Rotates `v` (assumes a unit quaternion).
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        let t = 2. * self.v.cross(v);
+        *v + self.w * t + self.v.cross(&t)
+    }
+
+    /// The angle in radians of the rotation from `self` to `other` (both unit quaternions).
+    pub fn angle_to(&self, other: &Quaternion) -> Float {
+        2. * self.dot(other).abs().min(1.).acos()
+    }
+
+    /// Spherical linear interpolation along the shortest arc.
+    pub fn slerp(&self, other: &Quaternion, t: Float) -> Quaternion {
+        let mut cos_theta = self.dot(other);
+        let mut other = *other;
+        if cos_theta < 0. {
+            cos_theta = -cos_theta;
+            other = Quaternion::new(-other.w, -other.v);
+        }
+        if cos_theta > 0.9995 {
+            // Nearly parallel; fall back to normalized linear interpolation.
+            return Quaternion::new(
+                (1. - t) * self.w + t * other.w,
+                (1. - t) * self.v + t * other.v,
+            )
+            .normalized();
+        }
+        let theta = cos_theta.acos();
+        let sin_theta = theta.sin();
+        let a = ((1. - t) * theta).sin() / sin_theta;
+        let b = (t * theta).sin() / sin_theta;
+        Quaternion::new(a * self.w + b * other.w, a * self.v + b * other.v)
+    }
+
+    pub fn to_matrix(&self) -> Matrix4 {
+        let (w, x, y, z) = (self.w, self.v.x(), self.v.y(), self.v.z());
+        Matrix4::new([
+            [
+                1. - 2. * (y * y + z * z),
+                2. * (x * y - w * z),
+                2. * (x * z + w * y),
+                0.,
+            ],
+            [
+                2. * (x * y + w * z),
+                1. - 2. * (x * x + z * z),
+                2. * (y * z - w * x),
+                0.,
+            ],
+            [
+                2. * (x * z - w * y),
+                2. * (y * z + w * x),
+                1. - 2. * (x * x + y * y),
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion::new(
+            self.w * other.w - self.v.dot(&other.v),
+            self.w * other.v + other.w * self.v + self.v.cross(&other.v),
+        )
+    }
+}
@@ -6,6 +6,9 @@ pub struct Ray {
     origin: Vec3,
     direction: Vec3,
     time: Float,
+    /// The wavelength in nanometers once a path has been restricted to a single wavelength
+    /// (e.g. by dispersion); `None` for RGB paths.
+    wavelength: Option<Float>,
 }
 
 impl Ray {
@@ -14,9 +17,21 @@ impl Ray {
             origin,
             direction,
             time,
+            wavelength: None,
         }
     }
 
+    pub fn with_wavelength(mut self, wavelength: Option<Float>) -> Ray {
+        self.wavelength = wavelength;
+        self
+    }
+
+    /// A ray from `origin` in `direction` which continues the path of `self` (at the same time
+    /// and wavelength).
+    pub fn spawn(&self, origin: Vec3, direction: Vec3) -> Ray {
+        Ray::new(origin, direction, self.time).with_wavelength(self.wavelength)
+    }
+
     pub fn origin(&self) -> Vec3 {
         self.origin
     }
@@ -28,6 +43,10 @@ impl Ray {
     pub fn time(&self) -> Float {
         self.time
     }
+
+    pub fn wavelength(&self) -> Option<Float> {
+        self.wavelength
+    }
 }
 
 impl Ray {
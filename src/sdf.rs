@@ -0,0 +1,191 @@
+use crate::math::clamp;
+use crate::math::float::Float;
+use crate::math::partial_max;
+use crate::math::partial_min;
+use crate::vec3::Vec3;
+
+pub trait Distance {
+    /// The signed distance from `p` to the surface (negative inside). Operations like the smooth
+    /// ones and fractals only return a bound which is good enough for sphere tracing.
+    fn distance(&self, p: &Vec3) -> Float;
+}
+
+/// A signed distance field built from primitives centered at the origin and operations on them.
+#[derive(Debug, Clone)]
+pub enum Sdf {
+    Sphere {
+        radius: Float,
+    },
+    /// A box with `half_extents` whose edges are rounded with `radius`.
+    RoundedBox {
+        half_extents: Vec3,
+        radius: Float,
+    },
+    /// A torus around the y axis.
+    Torus {
+        major_radius: Float,
+        minor_radius: Float,
+    },
+    Mandelbulb {
+        power: Float,
+        iterations: usize,
+    },
+    Translate(Box<Sdf>, Vec3),
+    Scale(Box<Sdf>, Float),
+    Union(Box<Sdf>, Box<Sdf>),
+    Intersection(Box<Sdf>, Box<Sdf>),
+    Difference(Box<Sdf>, Box<Sdf>),
+    /// Union blending both surfaces within a distance of roughly `k`.
+    SmoothUnion(Box<Sdf>, Box<Sdf>, Float),
+    SmoothIntersection(Box<Sdf>, Box<Sdf>, Float),
+    SmoothDifference(Box<Sdf>, Box<Sdf>, Float),
+    /// Infinite repetition with the given period along each axis (0 disables an axis). The
+    /// repeated field must fit into one cell.
+    Repeat(Box<Sdf>, Vec3),
+}
+
+impl Sdf {
+    pub fn sphere(radius: Float) -> Sdf {
+        Sdf::Sphere { radius }
+    }
+
+    pub fn rounded_box(half_extents: Vec3, radius: Float) -> Sdf {
+        Sdf::RoundedBox {
+            half_extents,
+            radius,
+        }
+    }
+
+    pub fn torus(major_radius: Float, minor_radius: Float) -> Sdf {
+        Sdf::Torus {
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    /// The Mandelbulb fractal which fits into a sphere of radius 1.2 for the usual `power` of 8.
+    pub fn mandelbulb(power: Float, iterations: usize) -> Sdf {
+        Sdf::Mandelbulb { power, iterations }
+    }
+
+    pub fn translate(sdf: Sdf, offset: Vec3) -> Sdf {
+        Sdf::Translate(Box::new(sdf), offset)
+    }
+
+    pub fn scale(sdf: Sdf, factor: Float) -> Sdf {
+        Sdf::Scale(Box::new(sdf), factor)
+    }
+
+    pub fn union(a: Sdf, b: Sdf) -> Sdf {
+        Sdf::Union(Box::new(a), Box::new(b))
+    }
+
+    pub fn intersection(a: Sdf, b: Sdf) -> Sdf {
+        Sdf::Intersection(Box::new(a), Box::new(b))
+    }
+
+    /// `a` without `b`.
+    pub fn difference(a: Sdf, b: Sdf) -> Sdf {
+        Sdf::Difference(Box::new(a), Box::new(b))
+    }
+
+    pub fn smooth_union(a: Sdf, b: Sdf, k: Float) -> Sdf {
+        Sdf::SmoothUnion(Box::new(a), Box::new(b), k)
+    }
+
+    pub fn smooth_intersection(a: Sdf, b: Sdf, k: Float) -> Sdf {
+        Sdf::SmoothIntersection(Box::new(a), Box::new(b), k)
+    }
+
+    /// `a` without `b`.
+    pub fn smooth_difference(a: Sdf, b: Sdf, k: Float) -> Sdf {
+        Sdf::SmoothDifference(Box::new(a), Box::new(b), k)
+    }
+
+    pub fn repeat(sdf: Sdf, period: Vec3) -> Sdf {
+        Sdf::Repeat(Box::new(sdf), period)
+    }
+}
+
+impl Distance for Sdf {
+    fn distance(&self, p: &Vec3) -> Float {
+        match self {
+            Sdf::Sphere { radius } => p.length() - radius,
+            Sdf::RoundedBox {
+                half_extents,
+                radius,
+            } => {
+                let q = Vec3::new(p.x().abs(), p.y().abs(), p.z().abs()) - *half_extents
+                    + Vec3::new(*radius, *radius, *radius);
+                let outside = Vec3::new(
+                    partial_max(q.x(), 0.),
+                    partial_max(q.y(), 0.),
+                    partial_max(q.z(), 0.),
+                );
+                let inside = partial_min(partial_max(q.x(), partial_max(q.y(), q.z())), 0.);
+                outside.length() + inside - radius
+            }
+            Sdf::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - major_radius;
+                (ring * ring + p.y() * p.y()).sqrt() - minor_radius
+            }
+            Sdf::Mandelbulb { power, iterations } => mandelbulb(p, *power, *iterations),
+            Sdf::Translate(sdf, offset) => sdf.distance(&(*p - *offset)),
+            Sdf::Scale(sdf, factor) => sdf.distance(&(*p / *factor)) * factor,
+            Sdf::Union(a, b) => partial_min(a.distance(p), b.distance(p)),
+            Sdf::Intersection(a, b) => partial_max(a.distance(p), b.distance(p)),
+            Sdf::Difference(a, b) => partial_max(a.distance(p), -b.distance(p)),
+            Sdf::SmoothUnion(a, b, k) => smooth_min(a.distance(p), b.distance(p), *k),
+            Sdf::SmoothIntersection(a, b, k) => smooth_max(a.distance(p), b.distance(p), *k),
+            Sdf::SmoothDifference(a, b, k) => smooth_max(a.distance(p), -b.distance(p), *k),
+            Sdf::Repeat(sdf, period) => {
+                let mut q = *p;
+                for i in 0..3 {
+                    if period[i] != 0. {
+                        q[i] -= period[i] * (p[i] / period[i]).round();
+                    }
+                }
+                sdf.distance(&q)
+            }
+        }
+    }
+}
+
+/// Polynomial smooth minimum (see Inigo Quilez, "smooth minimum").
+pub fn smooth_min(a: Float, b: Float, k: Float) -> Float {
+    let h = clamp(0.5 + 0.5 * (b - a) / k, 0., 1.);
+    b * (1. - h) + a * h - k * h * (1. - h)
+}
+
+pub fn smooth_max(a: Float, b: Float, k: Float) -> Float {
+    -smooth_min(-a, -b, k)
+}
+
+fn mandelbulb(p: &Vec3, power: Float, iterations: usize) -> Float {
+    if p.length_squared() == 0. {
+        return 0.;
+    }
+    let mut z = *p;
+    let mut dr = 1.;
+    let mut r = 0.;
+    for _ in 0..iterations {
+        r = z.length();
+        if r > 2. {
+            break;
+        }
+        let theta = (z.z() / r).acos() * power;
+        let phi = z.y().atan2(z.x()) * power;
+        dr = r.powf(power - 1.) * power * dr + 1.;
+        z = r.powf(power)
+            * Vec3::new(
+                theta.sin() * phi.cos(),
+                phi.sin() * theta.sin(),
+                theta.cos(),
+            )
+            + *p;
+    }
+    0.5 * r.ln() * r / dr
+}
@@ -0,0 +1,80 @@
+use std::marker::PhantomData;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::math::continue_past;
+use crate::math::float::Float;
+use crate::ray::Ray;
+use crate::texture::{Sample, Texture};
+use crate::Rng;
+
+/// How `AlphaMask` decides whether to keep a hit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    /// Hits with an opacity below the threshold are ignored.
+    Threshold(Float),
+    /// Hits are kept with the probability of their opacity which renders semi-transparent
+    /// surfaces without sorting.
+    Stochastic,
+}
+
+/// Cuts holes into a shape (e.g. the leaves of a foliage card or a chain-link fence on a quad).
+/// The opacity is the mean of the color channels of the texture at the `u` and `v` of a hit.
+/// Rays continue through ignored hits.
+#[derive(Debug)]
+pub struct AlphaMask<R: Rng, T: Hit<R>> {
+    inner: T,
+    alpha: Texture,
+    mode: AlphaMode,
+    phantom_data: PhantomData<R>,
+}
+
+impl<R: Rng, T: Hit<R>> AlphaMask<R, T> {
+    /// Ignores hits with an opacity below `threshold`.
+    pub fn new(inner: T, alpha: Texture, threshold: Float) -> AlphaMask<R, T> {
+        AlphaMask {
+            inner,
+            alpha,
+            mode: AlphaMode::Threshold(threshold),
+            phantom_data: PhantomData,
+        }
+    }
+
+    /// Keeps hits with the probability of their opacity (see `AlphaMode::Stochastic`).
+    pub fn stochastic(inner: T, alpha: Texture) -> AlphaMask<R, T> {
+        AlphaMask {
+            inner,
+            alpha,
+            mode: AlphaMode::Stochastic,
+            phantom_data: PhantomData,
+        }
+    }
+
+    fn opacity(&self, hit_record: &HitRecord<'_>) -> Float {
+        let alpha = self.alpha.sample(hit_record.u, hit_record.v, &hit_record.p);
+        (alpha.r() + alpha.g() + alpha.b()) / 3.
+    }
+}
+
+impl<R: Rng, T: Hit<R>> Hit<R> for AlphaMask<R, T> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, rng: &mut R) -> Option<HitRecord<'_>> {
+        let mut t_min = t_min;
+        loop {
+            let hit_record = self.inner.hit(ray, t_min, t_max, rng)?;
+            let opacity = self.opacity(&hit_record);
+            let opaque = match self.mode {
+                AlphaMode::Threshold(threshold) => opacity >= threshold,
+                AlphaMode::Stochastic => rng.gen::<Float>() < opacity,
+            };
+            if opaque {
+                return Some(hit_record);
+            }
+            // Continue past the ignored hit so it isn't found again.
+            t_min = continue_past(hit_record.t);
+        }
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        self.inner.bounding_box(time_start, time_end)
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::Material;
+use crate::math::float::consts::PI;
+use crate::math::float::Float;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::shape::cylinder::{angle_u, disk_extent, LocalHit};
+use crate::shape::disk::disk_derivatives;
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A cone with a circular base of `radius` at `base` and its tip at `apex`, optionally closed by
+/// a disk at the base.
+///
+/// On the side `u` is the angle around the axis and `v` the height (both normalized to
+/// `[0, 1]`). On the cap `u` is the angle and `v` the distance to the axis.
+#[derive(Debug, Clone)]
+pub struct Cone {
+    base: Vec3,
+    apex: Vec3,
+    height: Float,
+    radius: Float,
+    capped: bool,
+    frame: Onb,
+    material: Arc<Material>,
+}
+
+impl Cone {
+    pub fn new(base: Vec3, apex: Vec3, radius: Float, material: Arc<Material>) -> Cone {
+        let axis = apex - base;
+        Cone {
+            base,
+            apex,
+            height: axis.length(),
+            radius,
+            capped: false,
+            frame: Onb::from_w(&axis.unit_vector()),
+            material,
+        }
+    }
+
+    pub fn new_capped(base: Vec3, apex: Vec3, radius: Float, material: Arc<Material>) -> Cone {
+        Cone {
+            capped: true,
+            ..Cone::new(base, apex, radius, material)
+        }
+    }
+}
+
+impl<R: Rng> Hit<R> for Cone {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
+        // Intersect in a local frame where the axis is the z axis starting at the origin. The
+        // side is x^2 + y^2 = k^2 (h - z)^2 with k = radius / height.
+        let o = self.frame.to_local(&(ray.origin() - self.base));
+        let d = self.frame.to_local(&ray.direction());
+        let h = self.height;
+        let k_squared = (self.radius / h).powi(2);
+
+        let mut closest: Option<LocalHit> = None;
+        let mut consider = |t: Float, normal: Vec3, u: Float, v: Float, derivatives| {
+            if t > t_min && t < closest.map_or(t_max, |c| c.0) {
+                closest = Some((t, normal, u, v, derivatives));
+            }
+        };
+
+        let a = d.x() * d.x() + d.y() * d.y() - k_squared * d.z() * d.z();
+        let b = o.x() * d.x() + o.y() * d.y() + k_squared * (h - o.z()) * d.z();
+        let c = o.x() * o.x() + o.y() * o.y() - k_squared * (h - o.z()).powi(2);
+        let discriminant = b * b - a * c;
+        if a != 0. && discriminant > 0. {
+            for &t in &[
+                (-b - discriminant.sqrt()) / a,
+                (-b + discriminant.sqrt()) / a,
+            ] {
+                let p = o + t * d;
+                if p.z() >= 0. && p.z() <= h {
+                    let normal = Vec3::new(p.x(), p.y(), k_squared * (h - p.z())).unit_vector();
+                    let dpdu = 2. * PI * Vec3::new(-p.y(), p.x(), 0.);
+                    // The radius shrinks linearly towards the apex.
+                    let dpdv = if p.z() < h {
+                        h * Vec3::new(-p.x() / (h - p.z()), -p.y() / (h - p.z()), 1.)
+                    } else {
+                        Vec3::new(0., 0., h)
+                    };
+                    consider(t, normal, angle_u(&p), p.z() / h, (dpdu, dpdv));
+                }
+            }
+        }
+
+        if self.capped && d.z() != 0. {
+            let t = -o.z() / d.z();
+            let p = o + t * d;
+            let distance_squared = p.x() * p.x() + p.y() * p.y();
+            if distance_squared <= self.radius * self.radius {
+                let normal = Vec3::new(0., 0., -1.);
+                consider(
+                    t,
+                    normal,
+                    angle_u(&p),
+                    distance_squared.sqrt() / self.radius,
+                    disk_derivatives(&p, self.radius),
+                );
+            }
+        }
+
+        closest.map(|(t, normal, u, v, (dpdu, dpdv))| HitRecord {
+            t,
+            u,
+            v,
+            p: ray.point_at_parameter(t),
+            normal: self.frame.to_world(&normal),
+            dpdu: self.frame.to_world(&dpdu),
+            dpdv: self.frame.to_world(&dpdv),
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        let disk = disk_extent(&self.frame.w(), self.radius);
+        let base = Aabb::new(self.base - disk, self.base + disk);
+        Some(base.union(&Aabb::new(self.apex, self.apex)))
+    }
+}
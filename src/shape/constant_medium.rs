@@ -50,6 +50,8 @@ impl<R: Rng, T: Hit<R>> Hit<R> for ConstantMedium<R, T> {
                             t,
                             p: ray.point_at_parameter(t),
                             normal: Vec3::new(1., 0., 0.), // arbitrary
+                            dpdu: Vec3::zero(),
+                            dpdv: Vec3::zero(),
                             material: &(self.phase_function),
                             u: 0.0,
                             v: 0.0,
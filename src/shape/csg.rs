@@ -0,0 +1,177 @@
+use std::marker::PhantomData;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::math::float::{self, Float};
+use crate::math::{continue_past, partial_max, partial_min};
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    /// Everything inside of the left but not inside of the right operand.
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Constructive solid geometry: combines two closed objects (whose normals point outwards).
+///
+/// All surface crossings of both operands along the ray are classified as entering or exiting
+/// (by the direction of the normal) and the first crossing where the combined solid changes
+/// between inside and outside is the hit. Its material is the one of the operand which was
+/// crossed.
+#[derive(Debug)]
+pub struct Csg<R: Rng, A: Hit<R>, B: Hit<R>> {
+    left: A,
+    right: B,
+    operation: CsgOperation,
+    phantom_data: PhantomData<R>,
+}
+
+impl<R: Rng, A: Hit<R>, B: Hit<R>> Csg<R, A, B> {
+    pub fn new(left: A, right: B, operation: CsgOperation) -> Csg<R, A, B> {
+        Csg {
+            left,
+            right,
+            operation,
+            phantom_data: PhantomData,
+        }
+    }
+
+    pub fn union(left: A, right: B) -> Csg<R, A, B> {
+        Csg::new(left, right, CsgOperation::Union)
+    }
+
+    pub fn intersection(left: A, right: B) -> Csg<R, A, B> {
+        Csg::new(left, right, CsgOperation::Intersection)
+    }
+
+    pub fn difference(left: A, right: B) -> Csg<R, A, B> {
+        Csg::new(left, right, CsgOperation::Difference)
+    }
+}
+
+struct Crossing<'a> {
+    entering: bool,
+    hit_record: HitRecord<'a>,
+}
+
+/// Lazily walks the crossings of the whole line through a ray (sorted by `t`) so we know whether
+/// the line starts inside of the object at `t_min`. `peek` keeps the next crossing around so two
+/// of these can be merged.
+struct Crossings<'a, R: Rng, T: Hit<R>> {
+    object: &'a T,
+    t: Float,
+    next: Option<Crossing<'a>>,
+    phantom_data: PhantomData<R>,
+}
+
+impl<'a, R: Rng, T: Hit<R>> Crossings<'a, R, T> {
+    fn new(object: &'a T) -> Crossings<'a, R, T> {
+        Crossings {
+            object,
+            t: float::MIN,
+            next: None,
+            phantom_data: PhantomData,
+        }
+    }
+
+    fn peek(&mut self, ray: &Ray, t_max: Float, rng: &mut R) -> Option<&Crossing<'a>> {
+        if self.next.is_none() && self.t < t_max {
+            self.next = self
+                .object
+                .hit(ray, self.t, t_max, rng)
+                .map(|hit_record| Crossing {
+                    entering: ray.direction().dot(&hit_record.normal) < 0.,
+                    hit_record,
+                });
+            self.t = match &self.next {
+                Some(crossing) => continue_past(crossing.hit_record.t),
+                None => float::INFINITY,
+            };
+        }
+        self.next.as_ref()
+    }
+
+    fn take(&mut self) -> Option<Crossing<'a>> {
+        self.next.take()
+    }
+}
+
+impl<R: Rng, A: Hit<R>, B: Hit<R>> Hit<R> for Csg<R, A, B> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, rng: &mut R) -> Option<HitRecord<'_>> {
+        let mut left = Crossings::new(&self.left);
+        let mut right = Crossings::new(&self.right);
+
+        // Merge the crossings of both operands until the combined solid changes between inside
+        // and outside after `t_min`.
+        let (mut in_left, mut in_right) = (false, false);
+        loop {
+            let left_t = left.peek(ray, t_max, rng).map(|c| c.hit_record.t);
+            let right_t = right.peek(ray, t_max, rng).map(|c| c.hit_record.t);
+            let from_left = match (left_t, right_t) {
+                (Some(l), Some(r)) => l <= r,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+            let was_inside = self.operation.inside(in_left, in_right);
+            let crossing = if from_left {
+                let crossing = left.take().unwrap();
+                in_left = crossing.entering;
+                crossing
+            } else {
+                let crossing = right.take().unwrap();
+                in_right = crossing.entering;
+                crossing
+            };
+            let is_inside = self.operation.inside(in_left, in_right);
+
+            if was_inside != is_inside && crossing.hit_record.t > t_min {
+                let mut hit_record = crossing.hit_record;
+                // The surface of the right operand bounds the difference from the inside.
+                if !from_left && self.operation == CsgOperation::Difference {
+                    hit_record.normal = -hit_record.normal;
+                }
+                return Some(hit_record);
+            }
+        }
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        let left = self.left.bounding_box(time_start, time_end);
+        let right = self.right.bounding_box(time_start, time_end);
+        match self.operation {
+            CsgOperation::Union => Some(left?.union(&right?)),
+            CsgOperation::Intersection => match (left, right) {
+                (Some(l), Some(r)) => Some(Aabb::new(
+                    Vec3::new(
+                        partial_max(l.min.x(), r.min.x()),
+                        partial_max(l.min.y(), r.min.y()),
+                        partial_max(l.min.z(), r.min.z()),
+                    ),
+                    Vec3::new(
+                        partial_min(l.max.x(), r.max.x()),
+                        partial_min(l.max.y(), r.max.y()),
+                        partial_min(l.max.z(), r.max.z()),
+                    ),
+                )),
+                (Some(aabb), None) | (None, Some(aabb)) => Some(aabb),
+                (None, None) => None,
+            },
+            CsgOperation::Difference => left,
+        }
+    }
+}
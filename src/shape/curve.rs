@@ -0,0 +1,284 @@
+use std::sync::Arc;
+
+use crate::animation::split_bezier;
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::Material;
+use crate::math::float::consts::SQRT_2;
+use crate::math::float::Float;
+use crate::math::{clamp, partial_max};
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// The maximum number of times a curve is split in half during intersection.
+const MAX_SUBDIVISION_DEPTH: Float = 10.;
+
+/// How the surface of a `Curve` is oriented.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurveType {
+    /// A flat strip which always faces the ray.
+    Flat,
+    /// A flat strip which always faces the ray but whose normals are those of a round tube.
+    Cylinder,
+    /// A strip whose normal is interpolated between the normals at the start and the end.
+    Ribbon([Vec3; 2]),
+}
+
+/// A thin strand along a cubic Bezier curve (for hair, fur or grass). The width is interpolated
+/// linearly between both ends.
+///
+/// `u` goes from 0 to 1 along the curve and `v` from 0 to 1 across it, `dpdu` is the tangent of
+/// the curve. The intersection follows pbrt: the curve is transformed into a space where the
+/// ray is the z axis and recursively split until the pieces are nearly straight.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    control_points: [Vec3; 4],
+    width: (Float, Float),
+    curve_type: CurveType,
+    material: Arc<Material>,
+}
+
+struct CurveHit {
+    z: Float,
+    u: Float,
+    v: Float,
+}
+
+impl Curve {
+    pub fn new(
+        control_points: [Vec3; 4],
+        width: (Float, Float),
+        curve_type: CurveType,
+        material: Arc<Material>,
+    ) -> Curve {
+        let curve_type = match curve_type {
+            CurveType::Ribbon([n0, n1]) => CurveType::Ribbon([n0.unit_vector(), n1.unit_vector()]),
+            curve_type => curve_type,
+        };
+        Curve {
+            control_points,
+            width,
+            curve_type,
+            material,
+        }
+    }
+
+    pub fn control_points(&self) -> &[Vec3; 4] {
+        &self.control_points
+    }
+
+    fn width_at(&self, u: Float) -> Float {
+        (1. - u) * self.width.0 + u * self.width.1
+    }
+
+    fn ribbon_normal(normals: &[Vec3; 2], u: Float) -> Vec3 {
+        ((1. - u) * normals[0] + u * normals[1]).unit_vector()
+    }
+
+    /// Intersects the part `[u_start, u_end]` of the curve with the z axis. `cp` are its control
+    /// points in ray space.
+    #[allow(clippy::too_many_arguments)]
+    fn intersect_segment(
+        &self,
+        direction: &Vec3,
+        cp: &[Vec3; 4],
+        u_start: Float,
+        u_end: Float,
+        depth: u32,
+        z_min: Float,
+        z_max: Float,
+    ) -> Option<CurveHit> {
+        let half_width = partial_max(self.width_at(u_start), self.width_at(u_end)) / 2.;
+        let bb = cp
+            .iter()
+            .fold(Aabb::empty(), |bb, p| bb.union(&Aabb::new(*p, *p)));
+        if bb.max.x() + half_width < 0.
+            || bb.min.x() - half_width > 0.
+            || bb.max.y() + half_width < 0.
+            || bb.min.y() - half_width > 0.
+            || bb.max.z() + half_width < z_min
+            || bb.min.z() - half_width > z_max
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let (first_half, second_half) = split_bezier(cp, 0.5);
+            let u_mid = (u_start + u_end) / 2.;
+            let first = self.intersect_segment(
+                direction,
+                &first_half,
+                u_start,
+                u_mid,
+                depth - 1,
+                z_min,
+                z_max,
+            );
+            let z_max = first.as_ref().map_or(z_max, |hit| hit.z);
+            let second = self.intersect_segment(
+                direction,
+                &second_half,
+                u_mid,
+                u_end,
+                depth - 1,
+                z_min,
+                z_max,
+            );
+            return second.or(first);
+        }
+
+        stats::count_primitive_test();
+
+        // The origin has to lie between the lines through both ends which are perpendicular to
+        // the segment.
+        let edge = (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x());
+        if edge < 0. {
+            return None;
+        }
+        let edge = (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x());
+        if edge < 0. {
+            return None;
+        }
+
+        // Approximate the segment by a line to find the closest point to the origin.
+        let segment = (cp[3].x() - cp[0].x(), cp[3].y() - cp[0].y());
+        let denominator = segment.0 * segment.0 + segment.1 * segment.1;
+        if denominator == 0. {
+            return None;
+        }
+        let w = clamp(
+            -(cp[0].x() * segment.0 + cp[0].y() * segment.1) / denominator,
+            0.,
+            1.,
+        );
+        let u = (1. - w) * u_start + w * u_end;
+
+        let mut hit_width = self.width_at(u);
+        if let CurveType::Ribbon(normals) = &self.curve_type {
+            hit_width *= Curve::ribbon_normal(normals, u).dot(direction).abs();
+        }
+
+        let (pc, dpcdw) = evaluate_bezier(cp, w);
+        let distance_squared = pc.x() * pc.x() + pc.y() * pc.y();
+        if distance_squared > hit_width * hit_width / 4. || pc.z() < z_min || pc.z() > z_max {
+            return None;
+        }
+
+        let distance = distance_squared.sqrt();
+        let edge = dpcdw.x() * -pc.y() + pc.x() * dpcdw.y();
+        let v = if edge > 0. {
+            0.5 + distance / hit_width
+        } else {
+            0.5 - distance / hit_width
+        };
+        Some(CurveHit { z: pc.z(), u, v })
+    }
+}
+
+impl<R: Rng> Hit<R> for Curve {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        let length = ray.direction().length();
+        let direction = ray.direction() / length;
+        let frame = Onb::from_w(&direction);
+        let to_ray_space = |p: &Vec3| frame.to_local(&(*p - ray.origin()));
+        let cp = [
+            to_ray_space(&self.control_points[0]),
+            to_ray_space(&self.control_points[1]),
+            to_ray_space(&self.control_points[2]),
+            to_ray_space(&self.control_points[3]),
+        ];
+
+        // Split often enough for the pieces to deviate from a line by less than 5% of the width.
+        let l0 = (0..2)
+            .map(|i| {
+                let d = cp[i] - 2. * cp[i + 1] + cp[i + 2];
+                partial_max(partial_max(d.x().abs(), d.y().abs()), d.z().abs())
+            })
+            .fold(0., partial_max);
+        let epsilon = partial_max(self.width.0, self.width.1) * 0.05;
+        let depth = if l0 > 0. {
+            clamp(
+                ((SQRT_2 * 6. * l0 / (8. * epsilon)).log2() / 2.).round(),
+                0.,
+                MAX_SUBDIVISION_DEPTH,
+            ) as u32
+        } else {
+            0
+        };
+
+        let curve_hit = self.intersect_segment(
+            &direction,
+            &cp,
+            0.,
+            1.,
+            depth,
+            t_min * length,
+            t_max * length,
+        )?;
+
+        let t = curve_hit.z / length;
+        let p = ray.point_at_parameter(t);
+        let (center, dpdu) = evaluate_bezier(&self.control_points, curve_hit.u);
+        let tangent = dpdu.unit_vector();
+        let facing = -direction;
+        let flat = (facing - facing.dot(&tangent) * tangent).unit_vector();
+        let normal = match &self.curve_type {
+            CurveType::Flat => flat,
+            CurveType::Cylinder => {
+                let side = tangent.cross(&flat);
+                let offset = clamp(
+                    (p - center).dot(&side) / (self.width_at(curve_hit.u) / 2.),
+                    -1.,
+                    1.,
+                );
+                offset * side + (1. - offset * offset).sqrt() * flat
+            }
+            CurveType::Ribbon(normals) => {
+                let normal = Curve::ribbon_normal(normals, curve_hit.u);
+                if normal.dot(&facing) < 0. {
+                    -normal
+                } else {
+                    normal
+                }
+            }
+        };
+
+        // Across the curve in the direction of increasing `v`
+        let mut dpdv = self.width_at(curve_hit.u) * normal.cross(&tangent).unit_vector();
+        if (p - center).dot(&dpdv) * (curve_hit.v - 0.5) < 0. {
+            dpdv = -dpdv;
+        }
+
+        Some(HitRecord {
+            t,
+            u: curve_hit.u,
+            v: curve_hit.v,
+            p,
+            normal,
+            dpdu,
+            dpdv,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        // A Bezier curve is contained in the convex hull of its control points.
+        let half_width = partial_max(self.width.0, self.width.1) / 2.;
+        let padding = Vec3::new(half_width, half_width, half_width);
+        let bb = self
+            .control_points
+            .iter()
+            .fold(Aabb::empty(), |bb, p| bb.union(&Aabb::new(*p, *p)));
+        Some(Aabb::new(bb.min - padding, bb.max + padding))
+    }
+}
+
+/// The point and the derivative of a cubic Bezier curve at `t`.
+fn evaluate_bezier(cp: &[Vec3; 4], t: Float) -> (Vec3, Vec3) {
+    let (first, second) = split_bezier(cp, t);
+    let derivative = 3. * (second[1] - first[2]);
+    (first[3], derivative)
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::Material;
+use crate::math::float::consts::PI;
+use crate::math::float::Float;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::shape::disk::disk_derivatives;
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A cylinder around the axis from `base` to `top`, optionally closed by disks at both ends.
+///
+/// On the side `u` is the angle around the axis and `v` the height (both normalized to
+/// `[0, 1]`). On the caps `u` is the angle and `v` the distance to the axis.
+#[derive(Debug, Clone)]
+pub struct Cylinder {
+    base: Vec3,
+    top: Vec3,
+    height: Float,
+    radius: Float,
+    capped: bool,
+    frame: Onb,
+    material: Arc<Material>,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3, top: Vec3, radius: Float, material: Arc<Material>) -> Cylinder {
+        let axis = top - base;
+        Cylinder {
+            base,
+            top,
+            height: axis.length(),
+            radius,
+            capped: false,
+            frame: Onb::from_w(&axis.unit_vector()),
+            material,
+        }
+    }
+
+    pub fn new_capped(base: Vec3, top: Vec3, radius: Float, material: Arc<Material>) -> Cylinder {
+        Cylinder {
+            capped: true,
+            ..Cylinder::new(base, top, radius, material)
+        }
+    }
+}
+
+impl<R: Rng> Hit<R> for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
+        // Intersect in a local frame where the axis is the z axis starting at the origin.
+        let o = self.frame.to_local(&(ray.origin() - self.base));
+        let d = self.frame.to_local(&ray.direction());
+
+        let mut closest: Option<LocalHit> = None;
+        let mut consider = |t: Float, normal: Vec3, u: Float, v: Float, derivatives| {
+            if t > t_min && t < closest.map_or(t_max, |c| c.0) {
+                closest = Some((t, normal, u, v, derivatives));
+            }
+        };
+
+        let a = d.x() * d.x() + d.y() * d.y();
+        if a > 0. {
+            let b = o.x() * d.x() + o.y() * d.y();
+            let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+            let discriminant = b * b - a * c;
+            if discriminant > 0. {
+                for &t in &[
+                    (-b - discriminant.sqrt()) / a,
+                    (-b + discriminant.sqrt()) / a,
+                ] {
+                    let p = o + t * d;
+                    if p.z() >= 0. && p.z() <= self.height {
+                        let normal = Vec3::new(p.x(), p.y(), 0.) / self.radius;
+                        let dpdu = 2. * PI * Vec3::new(-p.y(), p.x(), 0.);
+                        let dpdv = Vec3::new(0., 0., self.height);
+                        consider(t, normal, angle_u(&p), p.z() / self.height, (dpdu, dpdv));
+                    }
+                }
+            }
+        }
+
+        if self.capped && d.z() != 0. {
+            for &(z, normal_z) in &[(0., -1.), (self.height, 1.)] {
+                let t = (z - o.z()) / d.z();
+                let p = o + t * d;
+                let distance_squared = p.x() * p.x() + p.y() * p.y();
+                if distance_squared <= self.radius * self.radius {
+                    let normal = Vec3::new(0., 0., normal_z);
+                    consider(
+                        t,
+                        normal,
+                        angle_u(&p),
+                        distance_squared.sqrt() / self.radius,
+                        disk_derivatives(&p, self.radius),
+                    );
+                }
+            }
+        }
+
+        closest.map(|(t, normal, u, v, (dpdu, dpdv))| HitRecord {
+            t,
+            u,
+            v,
+            p: ray.point_at_parameter(t),
+            normal: self.frame.to_world(&normal),
+            dpdu: self.frame.to_world(&dpdu),
+            dpdv: self.frame.to_world(&dpdv),
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        let disk = disk_extent(&self.frame.w(), self.radius);
+        let ends = Aabb::new(self.base - disk, self.base + disk);
+        Some(ends.union(&Aabb::new(self.top - disk, self.top + disk)))
+    }
+}
+
+/// `t`, the normal, `u`, `v`, `dpdu` and `dpdv` (in the local frame) of a candidate hit.
+pub(crate) type LocalHit = (Float, Vec3, Float, Float, (Vec3, Vec3));
+
+/// Angle of `p` around the z axis normalized to `[0, 1]`.
+pub(crate) fn angle_u(p: &Vec3) -> Float {
+    (p.y().atan2(p.x()) + PI) / (2. * PI)
+}
+
+/// Half extent along each axis of a disk with the given unit normal.
+pub(crate) fn disk_extent(normal: &Vec3, radius: Float) -> Vec3 {
+    let extent = |c: Float| radius * (1. - c * c).max(0.).sqrt() + 0.0001;
+    Vec3::new(extent(normal.x()), extent(normal.y()), extent(normal.z()))
+}
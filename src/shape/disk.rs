@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord, SampleSurface};
+use crate::material::Material;
+use crate::math::float::consts::PI;
+use crate::math::float::Float;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A flat disk. `u` is the angle around the normal and `v` the distance to the center (both
+/// normalized to `[0, 1]`).
+#[derive(Debug, Clone)]
+pub struct Disk {
+    center: Vec3,
+    radius: Float,
+    frame: Onb,
+    material: Arc<Material>,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: Float, material: Arc<Material>) -> Disk {
+        Disk {
+            center,
+            radius,
+            frame: Onb::from_w(&normal.unit_vector()),
+            material,
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        self.center
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        self.frame.w()
+    }
+
+    pub fn radius(&self) -> Float {
+        self.radius
+    }
+}
+
+impl<R: Rng> Hit<R> for Disk {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
+        let normal = self.frame.w();
+        let denominator = normal.dot(&ray.direction());
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.center - ray.origin()).dot(&normal) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let p = ray.point_at_parameter(t);
+        let local = self.frame.to_local(&(p - self.center));
+        let distance_squared = local.x().powi(2) + local.y().powi(2);
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+
+        let phi = local.y().atan2(local.x());
+        let (dpdu, dpdv) = disk_derivatives(&local, self.radius);
+        Some(HitRecord {
+            t,
+            u: (phi + PI) / (2. * PI),
+            v: distance_squared.sqrt() / self.radius,
+            p,
+            normal,
+            dpdu: self.frame.to_world(&dpdu),
+            dpdv: self.frame.to_world(&dpdv),
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        // Extent of the disk along each axis: radius * sin(angle between normal and axis)
+        let n = self.frame.w();
+        let extent = |c: Float| self.radius * (1. - c * c).max(0.).sqrt() + 0.0001;
+        let extent = Vec3::new(extent(n.x()), extent(n.y()), extent(n.z()));
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+impl SampleSurface for Disk {
+    fn area(&self) -> Float {
+        PI * self.radius * self.radius
+    }
+
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+        let r = self.radius * rng.gen::<Float>().sqrt();
+        let phi = 2. * PI * rng.gen::<Float>();
+        let p = self.center
+            + self
+                .frame
+                .to_world(&Vec3::new(r * phi.cos(), r * phi.sin(), 0.));
+        (p, self.frame.w())
+    }
+}
+
+/// The partial derivatives of the position with respect to the angle (`u`) and the distance to
+/// the center (`v`) on a disk around the z axis for the local point `p`.
+pub(crate) fn disk_derivatives(p: &Vec3, radius: Float) -> (Vec3, Vec3) {
+    let distance = (p.x() * p.x() + p.y() * p.y()).sqrt();
+    let dpdu = 2. * PI * Vec3::new(-p.y(), p.x(), 0.);
+    let dpdv = if distance > 0. {
+        radius * Vec3::new(p.x(), p.y(), 0.) / distance
+    } else {
+        Vec3::new(radius, 0., 0.)
+    };
+    (dpdu, dpdv)
+}
@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::image::Image;
+use crate::material::Material;
+use crate::math::float::{self, Float};
+use crate::math::{partial_max, partial_min};
+use crate::ray::Ray;
+use crate::shape::triangle::{intersect_triangle, triangle_derivatives};
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A terrain given by a regular grid of heights. Each grid cell is split into two triangles with
+/// normals interpolated from the vertex normals.
+///
+/// Rays walk through the cells they cross with a 2D DDA and only test the triangles of cells
+/// whose height range they pass through. `u` and `v` go from 0 to 1 along x and z.
+#[derive(Debug, Clone)]
+pub struct Heightfield {
+    /// Heights in world units, `width` values per row and one row per z coordinate.
+    heights: Vec<Float>,
+    normals: Vec<Vec3>,
+    width: usize,
+    depth: usize,
+    min: Vec3,
+    size: Vec3,
+    cell_size: (Float, Float),
+    aabb: Aabb,
+    material: Arc<Material>,
+}
+
+impl Heightfield {
+    /// `grid[z][x]` is the height (usually in `[0, 1]`) of the vertex at `x`, `z`. The grid is
+    /// stretched to cover `size.x()` and `size.z()` starting at `min` and its heights are scaled
+    /// by `size.y()`. Panics if the grid has less than 2x2 vertices or isn't rectangular.
+    pub fn new(grid: &[Vec<Float>], min: Vec3, size: Vec3, material: Arc<Material>) -> Heightfield {
+        let depth = grid.len();
+        assert!(depth >= 2, "a heightfield needs at least 2x2 vertices");
+        let width = grid[0].len();
+        assert!(width >= 2, "a heightfield needs at least 2x2 vertices");
+        assert!(grid.iter().all(|row| row.len() == width));
+
+        let heights: Vec<Float> = grid
+            .iter()
+            .flat_map(|row| row.iter().map(|h| min.y() + h * size.y()))
+            .collect();
+        let cell_size = (
+            size.x() / (width - 1) as Float,
+            size.z() / (depth - 1) as Float,
+        );
+
+        let height = |x: usize, z: usize| heights[z * width + x];
+        let mut normals = Vec::with_capacity(width * depth);
+        for z in 0..depth {
+            for x in 0..width {
+                // Central differences (one-sided at the border)
+                let (x0, x1) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                let (z0, z1) = (z.saturating_sub(1), (z + 1).min(depth - 1));
+                let dh_dx = (height(x1, z) - height(x0, z)) / ((x1 - x0) as Float * cell_size.0);
+                let dh_dz = (height(x, z1) - height(x, z0)) / ((z1 - z0) as Float * cell_size.1);
+                normals.push(Vec3::new(-dh_dx, 1., -dh_dz).unit_vector());
+            }
+        }
+
+        let (min_height, max_height) = heights
+            .iter()
+            .fold((float::MAX, float::MIN), |(lo, hi), &h| {
+                (partial_min(lo, h), partial_max(hi, h))
+            });
+        let aabb = Aabb::new(
+            Vec3::new(min.x(), min_height - 0.0001, min.z()),
+            Vec3::new(min.x() + size.x(), max_height + 0.0001, min.z() + size.z()),
+        );
+
+        Heightfield {
+            heights,
+            normals,
+            width,
+            depth,
+            min,
+            size,
+            cell_size,
+            aabb,
+            material,
+        }
+    }
+
+    /// Uses the luminance of each pixel as height. The top row of the image ends up at
+    /// `min.z()`.
+    pub fn from_image(
+        image: &Image,
+        min: Vec3,
+        size: Vec3,
+        material: Arc<Material>,
+    ) -> Heightfield {
+        let grid: Vec<Vec<Float>> = (0..image.height())
+            .map(|y| {
+                (0..image.width())
+                    .map(|x| {
+                        let pixel = image.get_pixel(x, y);
+                        0.2126 * pixel.r() + 0.7152 * pixel.g() + 0.0722 * pixel.b()
+                    })
+                    .collect()
+            })
+            .collect();
+        Heightfield::new(&grid, min, size, material)
+    }
+
+    fn vertex(&self, x: usize, z: usize) -> Vec3 {
+        Vec3::new(
+            self.min.x() + x as Float * self.cell_size.0,
+            self.heights[z * self.width + x],
+            self.min.z() + z as Float * self.cell_size.1,
+        )
+    }
+
+    fn hit_cell(
+        &self,
+        ray: &Ray,
+        x: usize,
+        z: usize,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<HitRecord<'_>> {
+        let indices = [
+            (x, z),
+            (x + 1, z),
+            (x + 1, z + 1),
+            (x, z),
+            (x + 1, z + 1),
+            (x, z + 1),
+        ];
+        let mut closest = None;
+        let mut closest_t = t_max;
+        for triangle in indices.chunks(3) {
+            let vertices = [
+                self.vertex(triangle[0].0, triangle[0].1),
+                self.vertex(triangle[1].0, triangle[1].1),
+                self.vertex(triangle[2].0, triangle[2].1),
+            ];
+            if let Some((t, b1, b2)) = intersect_triangle(ray, &vertices, t_min, closest_t) {
+                let normal = |i: usize| self.normals[triangle[i].1 * self.width + triangle[i].0];
+                let normal =
+                    ((1. - b1 - b2) * normal(0) + b1 * normal(1) + b2 * normal(2)).unit_vector();
+                let p = ray.point_at_parameter(t);
+                let uv = |v: &Vec3| {
+                    (
+                        (v.x() - self.min.x()) / self.size.x(),
+                        (v.z() - self.min.z()) / self.size.z(),
+                    )
+                };
+                let (dpdu, dpdv) = triangle_derivatives(
+                    &vertices,
+                    &[uv(&vertices[0]), uv(&vertices[1]), uv(&vertices[2])],
+                    &normal,
+                );
+                closest_t = t;
+                closest = Some(HitRecord {
+                    t,
+                    u: (p.x() - self.min.x()) / self.size.x(),
+                    v: (p.z() - self.min.z()) / self.size.z(),
+                    p,
+                    normal,
+                    dpdu,
+                    dpdv,
+                    material: &self.material,
+                });
+            }
+        }
+        closest
+    }
+}
+
+impl<R: Rng> Hit<R> for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        let (t_start, t_end) = self.aabb.hit_interval(ray, t_min, t_max)?;
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let cells = (self.width - 1, self.depth - 1);
+
+        // Setup of the 2D DDA (Amanatides & Woo) over the cells in the xz plane
+        let start = ray.point_at_parameter(t_start);
+        let cell = |p: Float, min: Float, size: Float, n: usize| {
+            (((p - min) / size).floor().max(0.) as usize).min(n - 1)
+        };
+        let mut x = cell(start.x(), self.min.x(), self.cell_size.0, cells.0);
+        let mut z = cell(start.z(), self.min.z(), self.cell_size.1, cells.1);
+        let axis = |index: usize, o: Float, d: Float, min: Float, size: Float| {
+            if d > 0. {
+                ((min + (index + 1) as Float * size - o) / d, size / d)
+            } else if d < 0. {
+                ((min + index as Float * size - o) / d, -size / d)
+            } else {
+                (float::INFINITY, float::INFINITY)
+            }
+        };
+        let (mut t_next_x, t_delta_x) =
+            axis(x, origin.x(), direction.x(), self.min.x(), self.cell_size.0);
+        let (mut t_next_z, t_delta_z) =
+            axis(z, origin.z(), direction.z(), self.min.z(), self.cell_size.1);
+
+        let mut t_enter = t_start;
+        loop {
+            let t_exit = partial_min(partial_min(t_next_x, t_next_z), t_end);
+
+            // Skip cells whose height range the ray doesn't pass through.
+            let heights = [
+                self.heights[z * self.width + x],
+                self.heights[z * self.width + x + 1],
+                self.heights[(z + 1) * self.width + x],
+                self.heights[(z + 1) * self.width + x + 1],
+            ];
+            let cell_min = heights.iter().cloned().fold(float::MAX, partial_min);
+            let cell_max = heights.iter().cloned().fold(float::MIN, partial_max);
+            let y_enter = origin.y() + t_enter * direction.y();
+            let y_exit = origin.y() + t_exit * direction.y();
+            if partial_min(y_enter, y_exit) <= cell_max && partial_max(y_enter, y_exit) >= cell_min
+            {
+                if let Some(hit_record) = self.hit_cell(ray, x, z, t_min, t_max) {
+                    return Some(hit_record);
+                }
+            }
+
+            if t_exit >= t_end {
+                return None;
+            }
+            if t_next_x < t_next_z {
+                if direction.x() > 0. && x + 1 < cells.0 {
+                    x += 1;
+                } else if direction.x() < 0. && x > 0 {
+                    x -= 1;
+                } else {
+                    return None;
+                }
+                t_next_x += t_delta_x;
+            } else {
+                if direction.z() > 0. && z + 1 < cells.1 {
+                    z += 1;
+                } else if direction.z() < 0. && z > 0 {
+                    z -= 1;
+                } else {
+                    return None;
+                }
+                t_next_z += t_delta_z;
+            }
+            t_enter = t_exit;
+        }
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        Some(self.aabb)
+    }
+}
@@ -1,9 +1,13 @@
 use std::marker::PhantomData;
+use std::sync::Arc;
 
+use crate::animation::AnimatedTransform;
 use crate::bvh::Aabb;
 use crate::hit::Hit;
 use crate::hit::HitRecord;
-use crate::math::float::{self, Float};
+use crate::material::Material;
+use crate::math::float::Float;
+use crate::matrix::{transform_aabb, AffineTransform, Matrix4};
 use crate::ray::Ray;
 use crate::vec3::Vec3;
 use crate::Rng;
@@ -12,6 +16,15 @@ pub trait Transform<R: Rng>: Hit<R> + Sized {
     fn flip_normals(self) -> FlipNormals<R, Self>;
     fn translate(self, offset: Vec3) -> Translate<R, Self>;
     fn rotate_y(self, angle: Float) -> RotateY<R, Self>;
+    /// Rotates by `angle` degrees around `axis` (through the origin).
+    fn rotate(self, axis: Vec3, angle: Float) -> Transformed<R, Self>;
+    fn rotate_x(self, angle: Float) -> Transformed<R, Self>;
+    fn rotate_z(self, angle: Float) -> Transformed<R, Self>;
+    fn scale(self, factor: Vec3) -> Transformed<R, Self>;
+    /// Applies an arbitrary affine transformation. Panics if `matrix` isn't invertible.
+    fn transform(self, matrix: Matrix4) -> Transformed<R, Self>;
+    /// Applies a transformation which changes over time (motion blur).
+    fn animate(self, transform: AnimatedTransform) -> Animated<R, Self>;
 }
 
 impl<R: Rng, T: Hit<R>> Transform<R> for T {
@@ -33,6 +46,37 @@ impl<R: Rng, T: Hit<R>> Transform<R> for T {
     fn rotate_y(self, angle: Float) -> RotateY<R, T> {
         RotateY::new(self, angle)
     }
+
+    fn rotate(self, axis: Vec3, angle: Float) -> Transformed<R, T> {
+        self.transform(Matrix4::rotation(axis, angle))
+    }
+
+    fn rotate_x(self, angle: Float) -> Transformed<R, T> {
+        self.transform(Matrix4::rotation_x(angle))
+    }
+
+    fn rotate_z(self, angle: Float) -> Transformed<R, T> {
+        self.transform(Matrix4::rotation_z(angle))
+    }
+
+    fn scale(self, factor: Vec3) -> Transformed<R, T> {
+        self.transform(Matrix4::scaling(factor))
+    }
+
+    fn transform(self, matrix: Matrix4) -> Transformed<R, T> {
+        Transformed::new(
+            self,
+            AffineTransform::new(matrix).expect("transformation matrix isn't invertible"),
+        )
+    }
+
+    fn animate(self, transform: AnimatedTransform) -> Animated<R, T> {
+        Animated {
+            inner: self,
+            transform,
+            phantom_data: PhantomData,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -64,7 +108,7 @@ pub struct Translate<R: Rng, T: Hit<R>> {
 
 impl<R: Rng, T: Hit<R>> Hit<R> for Translate<R, T> {
     fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, rng: &mut R) -> Option<HitRecord<'_>> {
-        let offset_ray = Ray::new(ray.origin() - self.offset, ray.direction(), ray.time());
+        let offset_ray = ray.spawn(ray.origin() - self.offset, ray.direction());
         let mut hit_record = self.inner.hit(&offset_ray, t_min, t_max, rng);
         if let Some(hit_record) = hit_record.as_mut() {
             hit_record.p += self.offset;
@@ -82,7 +126,7 @@ impl<R: Rng, T: Hit<R>> Hit<R> for Translate<R, T> {
 #[derive(Debug)]
 pub struct RotateY<R: Rng, T: Hit<R>> {
     inner: T,
-    bounding_box: Option<Aabb>,
+    angle: Float,
     sin_theta: Float,
     cos_theta: Float,
     phantom_data: PhantomData<R>,
@@ -91,38 +135,11 @@ pub struct RotateY<R: Rng, T: Hit<R>> {
 impl<R: Rng, T: Hit<R>> RotateY<R, T> {
     pub fn new(inner: T, angle: Float) -> RotateY<R, T> {
         let radians = angle.to_radians();
-        let sin_theta = radians.sin();
-        let cos_theta = radians.cos();
-
         RotateY {
-            sin_theta,
-            cos_theta,
-            bounding_box: inner.bounding_box(0., 1.).map(|bb| {
-                let mut min = Vec3::new(float::MAX, float::MAX, float::MAX);
-                let mut max = Vec3::new(-float::MAX, -float::MAX, -float::MAX);
-                for i in 0..2 {
-                    for j in 0..2 {
-                        for k in 0..2 {
-                            let x = i as Float * bb.max.x() + (1 - i) as Float * bb.min.x();
-                            let y = j as Float * bb.max.y() + (1 - j) as Float * bb.min.y();
-                            let z = k as Float * bb.max.z() + (1 - k) as Float * bb.min.z();
-                            let new_x = cos_theta * x + sin_theta * z;
-                            let new_z = -sin_theta * x + cos_theta * z;
-                            let tester = Vec3::new(new_x, y, new_z);
-                            for c in 0..2 {
-                                if tester[c] > max[c] {
-                                    max[c] = tester[c]
-                                }
-                                if tester[c] < min[c] {
-                                    min[c] = tester[c]
-                                }
-                            }
-                        }
-                    }
-                }
-                Aabb::new(min, max)
-            }),
             inner,
+            angle,
+            sin_theta: radians.sin(),
+            cos_theta: radians.cos(),
             phantom_data: PhantomData,
         }
     }
@@ -139,12 +156,14 @@ impl<R: Rng, T: Hit<R>> Hit<R> for RotateY<R, T> {
         direction[0] = self.cos_theta * ray.direction()[0] - self.sin_theta * ray.direction()[2];
         direction[2] = self.sin_theta * ray.direction()[0] + self.cos_theta * ray.direction()[2];
 
-        let rotated = Ray::new(origin, direction, ray.time());
+        let rotated = ray.spawn(origin, direction);
         self.inner
             .hit(&rotated, t_min, t_max, rng)
             .map(|mut hit_record| {
                 let mut p = hit_record.p;
                 let mut normal = hit_record.normal;
+                let mut dpdu = hit_record.dpdu;
+                let mut dpdv = hit_record.dpdv;
 
                 p[0] = self.cos_theta * hit_record.p[0] + self.sin_theta * hit_record.p[2];
                 p[2] = -self.sin_theta * hit_record.p[0] + self.cos_theta * hit_record.p[2];
@@ -154,13 +173,154 @@ impl<R: Rng, T: Hit<R>> Hit<R> for RotateY<R, T> {
                 normal[2] =
                     -self.sin_theta * hit_record.normal[0] + self.cos_theta * hit_record.normal[2];
 
+                dpdu[0] = self.cos_theta * hit_record.dpdu[0] + self.sin_theta * hit_record.dpdu[2];
+                dpdu[2] =
+                    -self.sin_theta * hit_record.dpdu[0] + self.cos_theta * hit_record.dpdu[2];
+
+                dpdv[0] = self.cos_theta * hit_record.dpdv[0] + self.sin_theta * hit_record.dpdv[2];
+                dpdv[2] =
+                    -self.sin_theta * hit_record.dpdv[0] + self.cos_theta * hit_record.dpdv[2];
+
                 hit_record.p = p;
                 hit_record.normal = normal;
+                hit_record.dpdu = dpdu;
+                hit_record.dpdv = dpdv;
+                hit_record
+            })
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        self.inner
+            .bounding_box(time_start, time_end)
+            .map(|bb| transform_aabb(&Matrix4::rotation_y(self.angle), &bb))
+    }
+}
+
+/// Applies an affine transformation to the inner object. Rays are transformed into object space
+/// and the hit point and normal back into world space.
+#[derive(Debug)]
+pub struct Transformed<R: Rng, T: Hit<R>> {
+    inner: T,
+    transform: AffineTransform,
+    phantom_data: PhantomData<R>,
+}
+
+impl<R: Rng, T: Hit<R>> Transformed<R, T> {
+    pub fn new(inner: T, transform: AffineTransform) -> Transformed<R, T> {
+        Transformed {
+            inner,
+            transform,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<R: Rng, T: Hit<R>> Hit<R> for Transformed<R, T> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, rng: &mut R) -> Option<HitRecord<'_>> {
+        // The direction isn't normalized so `t` is the same in both spaces.
+        let object_ray = ray.spawn(
+            self.transform.inverse_transform_point(&ray.origin()),
+            self.transform.inverse_transform_vector(&ray.direction()),
+        );
+        self.inner
+            .hit(&object_ray, t_min, t_max, rng)
+            .map(|mut hit_record| {
+                hit_record.p = self.transform.transform_point(&hit_record.p);
+                hit_record.normal = self.transform.transform_normal(&hit_record.normal);
+                hit_record.dpdu = self.transform.transform_vector(&hit_record.dpdu);
+                hit_record.dpdv = self.transform.transform_vector(&hit_record.dpdv);
                 hit_record
             })
     }
 
-    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
-        self.bounding_box
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        self.inner
+            .bounding_box(time_start, time_end)
+            .map(|bb| self.transform.transform_aabb(&bb))
+    }
+}
+
+/// A transformed reference to geometry which is shared between many instances, e.g. a single
+/// `Bvh` of a tree which is placed a thousand times in a forest. Each instance only costs its
+/// transformation (and optionally a material which overrides the one of the geometry).
+#[derive(Debug)]
+pub struct Instance<R: Rng> {
+    geometry: Arc<dyn Hit<R>>,
+    transform: AffineTransform,
+    material: Option<Arc<Material>>,
+}
+
+impl<R: Rng> Instance<R> {
+    pub fn new(geometry: Arc<dyn Hit<R>>, transform: AffineTransform) -> Instance<R> {
+        Instance {
+            geometry,
+            transform,
+            material: None,
+        }
+    }
+
+    pub fn with_material(mut self, material: Arc<Material>) -> Instance<R> {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl<R: Rng> Hit<R> for Instance<R> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, rng: &mut R) -> Option<HitRecord<'_>> {
+        let object_ray = ray.spawn(
+            self.transform.inverse_transform_point(&ray.origin()),
+            self.transform.inverse_transform_vector(&ray.direction()),
+        );
+        self.geometry
+            .hit(&object_ray, t_min, t_max, rng)
+            .map(|mut hit_record| {
+                hit_record.p = self.transform.transform_point(&hit_record.p);
+                hit_record.normal = self.transform.transform_normal(&hit_record.normal);
+                hit_record.dpdu = self.transform.transform_vector(&hit_record.dpdu);
+                hit_record.dpdv = self.transform.transform_vector(&hit_record.dpdv);
+                if let Some(material) = &self.material {
+                    hit_record.material = material;
+                }
+                hit_record
+            })
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        self.geometry
+            .bounding_box(time_start, time_end)
+            .map(|bb| self.transform.transform_aabb(&bb))
+    }
+}
+
+/// Applies a keyframed transformation which is evaluated at the time of each ray.
+#[derive(Debug)]
+pub struct Animated<R: Rng, T: Hit<R>> {
+    inner: T,
+    transform: AnimatedTransform,
+    phantom_data: PhantomData<R>,
+}
+
+impl<R: Rng, T: Hit<R>> Hit<R> for Animated<R, T> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, rng: &mut R) -> Option<HitRecord<'_>> {
+        let transform = self.transform.at_time(ray.time());
+        let object_ray = ray.spawn(
+            transform.inverse_transform_point(&ray.origin()),
+            transform.inverse_transform_vector(&ray.direction()),
+        );
+        self.inner
+            .hit(&object_ray, t_min, t_max, rng)
+            .map(|mut hit_record| {
+                hit_record.p = transform.transform_point(&hit_record.p);
+                hit_record.normal = transform.transform_normal(&hit_record.normal);
+                hit_record.dpdu = transform.transform_vector(&hit_record.dpdu);
+                hit_record.dpdv = transform.transform_vector(&hit_record.dpdv);
+                hit_record
+            })
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        self.inner
+            .bounding_box(time_start, time_end)
+            .map(|bb| self.transform.bounding_box(&bb, time_start, time_end))
     }
 }
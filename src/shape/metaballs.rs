@@ -0,0 +1,276 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::Material;
+use crate::math::float::{self, Float};
+use crate::ray::Ray;
+use crate::shape::sphere::{sphere_derivatives, sphere_uv};
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// Subdivision stops once the field can change by less than this (relative to the threshold)
+/// within an interval.
+const ROOT_TOLERANCE: Float = 1e-4;
+const BISECTION_STEPS: usize = 32;
+
+/// Buffers of `Metaballs::hit` which are reused between rays.
+#[derive(Default)]
+struct Scratch {
+    /// The source and the interval of `t` in which the ray is inside its influence sphere.
+    intervals: Vec<(usize, Float, Float)>,
+    boundaries: Vec<Float>,
+    active: Vec<usize>,
+}
+
+thread_local! {
+    static SCRATCH: RefCell<Scratch> = RefCell::new(Scratch::default());
+}
+
+/// A point source of a `Metaballs` field. Its contribution falls off with the kernel of Wyvill
+/// et al., `weight * (1 - d² / radius²)³`, and is zero beyond `radius`. Negative weights carve
+/// into the surface.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metaball {
+    pub center: Vec3,
+    pub radius: Float,
+    pub weight: Float,
+}
+
+impl Metaball {
+    pub fn new(center: Vec3, radius: Float, weight: Float) -> Metaball {
+        Metaball {
+            center,
+            radius,
+            weight,
+        }
+    }
+
+    fn field(&self, p: &Vec3) -> Float {
+        let x = 1. - (*p - self.center).length_squared() / (self.radius * self.radius);
+        if x <= 0. {
+            0.
+        } else {
+            self.weight * x * x * x
+        }
+    }
+
+    fn gradient(&self, p: &Vec3) -> Vec3 {
+        let offset = *p - self.center;
+        let r2 = self.radius * self.radius;
+        let x = 1. - offset.length_squared() / r2;
+        if x <= 0. {
+            Vec3::zero()
+        } else {
+            (-6. * self.weight * x * x / r2) * offset
+        }
+    }
+
+    /// An upper bound of the slope of the field along a ray whose direction has the given
+    /// length. The slope of the kernel is `6 weight x² s / radius` (with `s` the distance to the
+    /// center relative to the radius and `x = 1 - s²`) which is largest at `s = 1 / √5`.
+    fn max_slope(&self, direction_length: Float) -> Float {
+        6. * 16. / (25. * (5. as Float).sqrt()) * self.weight.abs() * direction_length / self.radius
+    }
+
+    /// The interval of `t` in which the ray is inside the influence sphere.
+    fn interval(&self, ray: &Ray) -> Option<(Float, Float)> {
+        let oc = ray.origin() - self.center;
+        let a = ray.direction().length_squared();
+        let b = oc.dot(&ray.direction());
+        let c = oc.length_squared() - self.radius * self.radius;
+        let discriminant = b * b - a * c;
+        if discriminant <= 0. || a <= 0. {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (t0, t1) = ((-b - root) / a, (-b + root) / a);
+        if t0.is_nan() || t1.is_nan() {
+            return None;
+        }
+        Some((t0, t1))
+    }
+}
+
+/// A blobby implicit surface: the points where the summed fields of the sources equal
+/// `threshold`.
+///
+/// Along a ray the set of sources which contribute only changes at the boundaries of their
+/// influence spheres. Between these boundaries the slope of the field is bounded by the sum of
+/// the slopes of the kernels, so intervals where the field can't reach the threshold are skipped
+/// and the others are subdivided until the first sign change is isolated. It's then refined with
+/// bisection. Normals are given by the analytic gradient, the texture coordinates are derived from
+/// the normal like for a `Sphere`.
+#[derive(Debug, Clone)]
+pub struct Metaballs {
+    sources: Vec<Metaball>,
+    threshold: Float,
+    aabb: Aabb,
+    material: Arc<Material>,
+}
+
+impl Metaballs {
+    /// Panics if `threshold` isn't positive or there is no source with a positive weight.
+    pub fn new(sources: Vec<Metaball>, threshold: Float, material: Arc<Material>) -> Metaballs {
+        assert!(threshold > 0., "the threshold has to be positive");
+        // Sources with negative weights only remove volume so they don't extend the AABB.
+        let aabb = sources
+            .iter()
+            .filter(|s| s.weight > 0.)
+            .map(|s| {
+                let r = Vec3::new(s.radius, s.radius, s.radius);
+                Aabb::new(s.center - r, s.center + r)
+            })
+            .fold(None, |aabb: Option<Aabb>, bb| {
+                Some(aabb.map_or(bb, |aabb| aabb.union(&bb)))
+            })
+            .expect("at least one source with a positive weight is required");
+        Metaballs {
+            sources,
+            threshold,
+            aabb,
+            material,
+        }
+    }
+
+    pub fn sources(&self) -> &[Metaball] {
+        &self.sources
+    }
+
+    /// The field minus the threshold (positive inside) of the sources with the given indices.
+    fn value(&self, active: &[usize], p: &Vec3) -> Float {
+        active
+            .iter()
+            .map(|&i| self.sources[i].field(p))
+            .sum::<Float>()
+            - self.threshold
+    }
+
+    /// Finds the first subinterval of `[a, b]` (where the field minus the threshold is `fa` and
+    /// `fb`) in which `value_at` changes sign. `slope` bounds the slope of `value_at`.
+    fn isolate_root<F: Fn(Float) -> Float>(
+        &self,
+        value_at: &F,
+        slope: Float,
+        (a, fa): (Float, Float),
+        (b, fb): (Float, Float),
+    ) -> Option<(Float, Float)> {
+        let sign_change = (fa > 0.) != (fb > 0.);
+        let max_change = slope * (b - a);
+        // Without a sign change the field can only reach zero in between if its slope is large
+        // enough to come down to zero from both ends.
+        if !sign_change && fa.abs() + fb.abs() > max_change {
+            return None;
+        }
+        if max_change < ROOT_TOLERANCE * self.threshold || b - a <= float::EPSILON * b.abs() {
+            return if sign_change { Some((a, b)) } else { None };
+        }
+        let m = (a + b) / 2.;
+        let fm = value_at(m);
+        self.isolate_root(value_at, slope, (a, fa), (m, fm))
+            .or_else(|| self.isolate_root(value_at, slope, (m, fm), (b, fb)))
+    }
+
+    fn normal(&self, active: &[usize], p: &Vec3) -> Vec3 {
+        let gradient = active
+            .iter()
+            .fold(Vec3::zero(), |sum, &i| sum + self.sources[i].gradient(p));
+        // The field increases towards the inside.
+        -gradient.unit_vector()
+    }
+}
+
+impl<R: Rng> Hit<R> for Metaballs {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
+        self.aabb.hit_interval(ray, t_min, t_max)?;
+
+        SCRATCH.with(|scratch| {
+            let Scratch {
+                intervals,
+                boundaries,
+                active,
+            } = &mut *scratch.borrow_mut();
+            intervals.clear();
+            boundaries.clear();
+            for (i, source) in self.sources.iter().enumerate() {
+                if let Some((t0, t1)) = source.interval(ray) {
+                    let (t0, t1) = (t0.max(t_min), t1.min(t_max));
+                    if t0 < t1 {
+                        intervals.push((i, t0, t1));
+                        boundaries.push(t0);
+                        boundaries.push(t1);
+                    }
+                }
+            }
+            boundaries.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            boundaries.dedup();
+
+            let direction_length = ray.direction().length();
+            for segment in boundaries.windows(2) {
+                let (start, end) = (segment[0], segment[1]);
+                let middle = (start + end) / 2.;
+                active.clear();
+                active.extend(
+                    intervals
+                        .iter()
+                        .filter(|&&(_, t0, t1)| t0 <= middle && middle <= t1)
+                        .map(|&(i, _, _)| i),
+                );
+                if !active.iter().any(|&i| self.sources[i].weight > 0.) {
+                    continue;
+                }
+
+                let value_at = |t: Float| self.value(active, &ray.point_at_parameter(t));
+                let slope = active
+                    .iter()
+                    .map(|&i| self.sources[i].max_slope(direction_length))
+                    .sum();
+                let (mut low, mut high) = match self.isolate_root(
+                    &value_at,
+                    slope,
+                    (start, value_at(start)),
+                    (end, value_at(end)),
+                ) {
+                    Some(interval) => interval,
+                    None => continue,
+                };
+
+                // Bisection keeping the sign change inside of `[low, high]`
+                let low_inside = value_at(low) > 0.;
+                for _ in 0..BISECTION_STEPS {
+                    let middle = (low + high) / 2.;
+                    if (value_at(middle) > 0.) == low_inside {
+                        low = middle;
+                    } else {
+                        high = middle;
+                    }
+                }
+
+                let t = high;
+                let p = ray.point_at_parameter(t);
+                let normal = self.normal(active, &p);
+                let (u, v) = sphere_uv(normal);
+                let (dpdu, dpdv) = sphere_derivatives(normal, 1.);
+                return Some(HitRecord {
+                    t,
+                    u,
+                    v,
+                    p,
+                    normal,
+                    dpdu,
+                    dpdv,
+                    material: &self.material,
+                });
+            }
+            None
+        })
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        Some(self.aabb)
+    }
+}
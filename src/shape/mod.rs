@@ -1,11 +1,37 @@
+mod alpha_mask;
+mod cone;
 mod constant_medium;
+mod csg;
+mod curve;
+mod cylinder;
+mod disk;
+mod heightfield;
 mod instance;
+mod metaballs;
+mod plane;
+mod quad;
 mod rect;
 mod rect_box;
+mod sdf_shape;
 mod sphere;
+mod torus;
+mod triangle;
 
+pub use self::alpha_mask::*;
+pub use self::cone::*;
 pub use self::constant_medium::*;
+pub use self::csg::*;
+pub use self::curve::*;
+pub use self::cylinder::*;
+pub use self::disk::*;
+pub use self::heightfield::*;
 pub use self::instance::*;
+pub use self::metaballs::*;
+pub use self::plane::*;
+pub use self::quad::*;
 pub use self::rect::*;
 pub use self::rect_box::*;
+pub use self::sdf_shape::*;
 pub use self::sphere::*;
+pub use self::torus::*;
+pub use self::triangle::*;
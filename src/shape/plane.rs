@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::Material;
+use crate::math::float::Float;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// An infinite plane. It has no bounding box so a `Bvh` keeps it outside of its tree.
+///
+/// `u` and `v` are the coordinates along two axes in the plane, repeating every `uv_scale`
+/// units (1 by default), so textures tile across the plane.
+#[derive(Debug, Clone)]
+pub struct Plane {
+    point: Vec3,
+    frame: Onb,
+    uv_scale: Float,
+    material: Arc<Material>,
+}
+
+impl Plane {
+    pub fn new(point: Vec3, normal: Vec3, material: Arc<Material>) -> Plane {
+        Plane {
+            point,
+            frame: Onb::from_w(&normal.unit_vector()),
+            uv_scale: 1.,
+            material,
+        }
+    }
+
+    pub fn with_uv_scale(mut self, uv_scale: Float) -> Plane {
+        self.uv_scale = uv_scale;
+        self
+    }
+}
+
+impl<R: Rng> Hit<R> for Plane {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
+        let normal = self.frame.w();
+        let denominator = normal.dot(&ray.direction());
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.point - ray.origin()).dot(&normal) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let p = ray.point_at_parameter(t);
+        let local = self.frame.to_local(&(p - self.point)) / self.uv_scale;
+        Some(HitRecord {
+            t,
+            u: local.x() - local.x().floor(),
+            v: local.y() - local.y().floor(),
+            p,
+            normal,
+            dpdu: self.uv_scale * self.frame.u(),
+            dpdv: self.uv_scale * self.frame.v(),
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        None
+    }
+}
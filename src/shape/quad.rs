@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord, SampleSurface};
+use crate::material::Material;
+use crate::math::float::Float;
+use crate::ray::Ray;
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A parallelogram spanned by the edges `u` and `v` starting at `origin`. The normal is
+/// `u × v`.
+#[derive(Debug, Clone)]
+pub struct Quad {
+    origin: Vec3,
+    u: Vec3,
+    v: Vec3,
+    normal: Vec3,
+    d: Float,
+    w: Vec3,
+    area: Float,
+    material: Arc<Material>,
+}
+
+impl Quad {
+    pub fn new(origin: Vec3, u: Vec3, v: Vec3, material: Arc<Material>) -> Quad {
+        let n = u.cross(&v);
+        let normal = n.unit_vector();
+        Quad {
+            origin,
+            u,
+            v,
+            normal,
+            d: normal.dot(&origin),
+            w: n / n.dot(&n),
+            area: n.length(),
+            material,
+        }
+    }
+
+    pub fn origin(&self) -> Vec3 {
+        self.origin
+    }
+
+    pub fn u(&self) -> Vec3 {
+        self.u
+    }
+
+    pub fn v(&self) -> Vec3 {
+        self.v
+    }
+}
+
+impl<R: Rng> Hit<R> for Quad {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
+        let denominator = self.normal.dot(&ray.direction());
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+        let t = (self.d - self.normal.dot(&ray.origin())) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        // Express the hit point in the (u, v) coordinates of the plane.
+        let p = ray.point_at_parameter(t);
+        let planar = p - self.origin;
+        let alpha = self.w.dot(&planar.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar));
+        if !(0. ..=1.).contains(&alpha) || !(0. ..=1.).contains(&beta) {
+            return None;
+        }
+
+        Some(HitRecord {
+            t,
+            u: alpha,
+            v: beta,
+            p,
+            normal: self.normal,
+            dpdu: self.u,
+            dpdv: self.v,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        let aabb = [self.u, self.v, self.u + self.v]
+            .iter()
+            .map(|&e| self.origin + e)
+            .fold(Aabb::new(self.origin, self.origin), |aabb, p| {
+                aabb.union(&Aabb::new(p, p))
+            });
+        // Pad the AABB so it doesn't have zero thickness for axis-aligned quads.
+        let padding = Vec3::new(0.0001, 0.0001, 0.0001);
+        Some(Aabb::new(aabb.min - padding, aabb.max + padding))
+    }
+}
+
+impl SampleSurface for Quad {
+    fn area(&self) -> Float {
+        self.area
+    }
+
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+        let p = self.origin + rng.gen::<Float>() * self.u + rng.gen::<Float>() * self.v;
+        (p, self.normal)
+    }
+}
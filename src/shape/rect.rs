@@ -7,6 +7,7 @@ use crate::hit::{Hit, HitRecord};
 use crate::material::Material;
 use crate::math::float::Float;
 use crate::ray::Ray;
+use crate::stats;
 use crate::vec3::Vec3;
 use crate::Rng;
 
@@ -101,6 +102,7 @@ impl<A: Axis> GenericRect<A> {
 
 impl<R: Rng, A: Axis> Hit<R> for GenericRect<A> {
     fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
         let t = (self.c - A::c(&ray.origin())) / A::c(&ray.direction());
         if t < t_min || t > t_max {
             return None;
@@ -116,6 +118,8 @@ impl<R: Rng, A: Axis> Hit<R> for GenericRect<A> {
                 v: (b - self.b.0) / (self.b.1 - self.b.0),
                 p: ray.point_at_parameter(t),
                 normal: A::normal(),
+                dpdu: A::vector(self.a.1 - self.a.0, 0., 0.),
+                dpdv: A::vector(0., self.b.1 - self.b.0, 0.),
                 material: &self.material,
             })
         }
@@ -131,6 +135,8 @@ pub trait Axis: Debug + Sync + Send {
     fn b(vec3: &Vec3) -> Float;
     fn c(vec3: &Vec3) -> Float;
     fn normal() -> Vec3;
+    /// The vector with the given components along the axes `a`, `b` and `c`.
+    fn vector(a: Float, b: Float, c: Float) -> Vec3;
     fn bounding_box(a: (Float, Float), b: (Float, Float), c: Float) -> Aabb;
 }
 
@@ -150,6 +156,9 @@ impl Axis for XY {
     fn normal() -> Vec3 {
         Vec3::new(0., 0., 1.)
     }
+    fn vector(a: Float, b: Float, c: Float) -> Vec3 {
+        Vec3::new(a, b, c)
+    }
     fn bounding_box(a: (Float, Float), b: (Float, Float), c: Float) -> Aabb {
         Aabb::new(
             Vec3::new(a.0, b.0, c - 0.0001),
@@ -174,6 +183,9 @@ impl Axis for YZ {
     fn normal() -> Vec3 {
         Vec3::new(1., 0., 0.)
     }
+    fn vector(a: Float, b: Float, c: Float) -> Vec3 {
+        Vec3::new(c, a, b)
+    }
     fn bounding_box(a: (Float, Float), b: (Float, Float), c: Float) -> Aabb {
         Aabb::new(
             Vec3::new(c - 0.0001, a.0, b.0),
@@ -198,6 +210,9 @@ impl Axis for XZ {
     fn normal() -> Vec3 {
         Vec3::new(0., 1., 0.)
     }
+    fn vector(a: Float, b: Float, c: Float) -> Vec3 {
+        Vec3::new(a, c, b)
+    }
     fn bounding_box(a: (Float, Float), b: (Float, Float), c: Float) -> Aabb {
         Aabb::new(
             Vec3::new(a.0, c - 0.0001, b.0),
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::Material;
+use crate::math::float::Float;
+use crate::math::partial_max;
+use crate::ray::Ray;
+use crate::sdf::{Distance, Sdf};
+use crate::shape::sphere::{sphere_derivatives, sphere_uv};
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A surface given by a signed distance field which is rendered with sphere tracing inside of
+/// `aabb`. The texture coordinates are derived from the normal like for a `Sphere`.
+#[derive(Debug, Clone)]
+pub struct SdfShape {
+    sdf: Sdf,
+    aabb: Aabb,
+    material: Arc<Material>,
+    epsilon: Float,
+    max_steps: usize,
+    step_scale: Float,
+}
+
+impl SdfShape {
+    pub fn new(sdf: Sdf, aabb: Aabb, material: Arc<Material>) -> SdfShape {
+        SdfShape {
+            sdf,
+            aabb,
+            material,
+            epsilon: 0.0001,
+            max_steps: 512,
+            step_scale: 1.,
+        }
+    }
+
+    /// Distance to the surface which counts as a hit (and step size of the gradient estimation).
+    pub fn with_epsilon(mut self, epsilon: Float) -> SdfShape {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> SdfShape {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Scales each step of the sphere tracing. Values below 1 are needed for fields which
+    /// overestimate the distance (e.g. after strong smooth blending).
+    pub fn with_step_scale(mut self, step_scale: Float) -> SdfShape {
+        self.step_scale = step_scale;
+        self
+    }
+
+    /// Estimates the normal from the gradient of the field using central differences on a
+    /// tetrahedron.
+    fn normal(&self, p: &Vec3) -> Vec3 {
+        let h = self.epsilon;
+        let k0 = Vec3::new(1., -1., -1.);
+        let k1 = Vec3::new(-1., -1., 1.);
+        let k2 = Vec3::new(-1., 1., -1.);
+        let k3 = Vec3::new(1., 1., 1.);
+        let gradient = k0 * self.sdf.distance(&(*p + h * k0))
+            + k1 * self.sdf.distance(&(*p + h * k1))
+            + k2 * self.sdf.distance(&(*p + h * k2))
+            + k3 * self.sdf.distance(&(*p + h * k3));
+        gradient.unit_vector()
+    }
+}
+
+impl<R: Rng> Hit<R> for SdfShape {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
+        let (t_start, t_end) = self.aabb.hit_interval(ray, t_min, t_max)?;
+        let direction_length = ray.direction().length();
+        let min_step = self.epsilon / direction_length;
+
+        let mut t = t_start;
+        let mut steps = 0;
+        let mut distance = self.sdf.distance(&ray.point_at_parameter(t));
+        // Leave the surface the ray starts on (e.g. after a reflection) before searching for the
+        // next one.
+        while distance.abs() < self.epsilon && steps < self.max_steps {
+            t += min_step;
+            distance = self.sdf.distance(&ray.point_at_parameter(t));
+            steps += 1;
+        }
+        // Rays starting inside trace the negated field so they find the way out.
+        let sign = if distance < 0. { -1. } else { 1. };
+
+        while steps < self.max_steps && t <= t_end {
+            let distance = sign * self.sdf.distance(&ray.point_at_parameter(t));
+            if distance < self.epsilon {
+                let p = ray.point_at_parameter(t);
+                let normal = self.normal(&p);
+                let (u, v) = sphere_uv(normal);
+                let (dpdu, dpdv) = sphere_derivatives(normal, 1.);
+                return Some(HitRecord {
+                    t,
+                    u,
+                    v,
+                    p,
+                    normal,
+                    dpdu,
+                    dpdv,
+                    material: &self.material,
+                });
+            }
+            t += partial_max(self.step_scale * distance / direction_length, min_step);
+            steps += 1;
+        }
+        None
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        Some(self.aabb)
+    }
+}
@@ -1,29 +1,29 @@
 use std::sync::Arc;
 
+use crate::animation::MotionPath;
 use crate::bvh::Aabb;
 use crate::hit::{Hit, HitRecord};
 use crate::material::Material;
 use crate::math::float::consts::{FRAC_PI_2, PI};
 use crate::math::float::Float;
 use crate::ray::Ray;
+use crate::stats;
 use crate::vec3::Vec3;
 use crate::Rng;
 
 #[derive(Debug, Clone)]
 pub struct Sphere {
-    center: Vec3,
+    center: MotionPath,
     radius: Float,
     material: Arc<Material>,
-    motion_vector: Option<Vec3>,
 }
 
 impl Sphere {
     pub fn new(center: Vec3, radius: Float, material: Arc<Material>) -> Sphere {
         Sphere {
-            center,
+            center: MotionPath::Static(center),
             radius,
             material,
-            motion_vector: None,
         }
     }
 
@@ -34,23 +34,32 @@ impl Sphere {
         motion_vector: Vec3,
     ) -> Sphere {
         Sphere {
-            center,
+            center: MotionPath::linear(center, motion_vector),
             radius,
             material,
-            motion_vector: Some(motion_vector),
         }
     }
 
+    /// A sphere whose center follows `path`.
+    pub fn new_with_path(path: MotionPath, radius: Float, material: Arc<Material>) -> Sphere {
+        Sphere {
+            center: path,
+            radius,
+            material,
+        }
+    }
+
+    /// The center at time 0.
     pub fn center(&self) -> Vec3 {
-        self.center
+        self.center.position_at(0.)
     }
 
     pub fn center_at_time(&self, t: Float) -> Vec3 {
-        if let Some(motion_vector) = self.motion_vector {
-            self.center + t * motion_vector
-        } else {
-            self.center
-        }
+        self.center.position_at(t)
+    }
+
+    pub fn path(&self) -> &MotionPath {
+        &self.center
     }
 
     pub fn radius(&self) -> Float {
@@ -60,20 +69,31 @@ impl Sphere {
 
 impl<R: Rng> Hit<R> for Sphere {
     fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
-        fn calculate_hit_record<'a>(ray: &Ray, t: Float, sphere: &'a Sphere) -> HitRecord<'a> {
+        fn calculate_hit_record<'a>(
+            ray: &Ray,
+            t: Float,
+            center: Vec3,
+            sphere: &'a Sphere,
+        ) -> HitRecord<'a> {
             let p = ray.point_at_parameter(t);
-            let (u, v) = sphere_uv((p - sphere.center) / sphere.radius);
+            let normal = (p - center) / sphere.radius();
+            let (u, v) = sphere_uv(normal);
+            let (dpdu, dpdv) = sphere_derivatives(normal, sphere.radius());
             HitRecord {
                 t,
                 u,
                 v,
                 p,
-                normal: (p - sphere.center_at_time(ray.time())) / sphere.radius(),
+                normal,
+                dpdu,
+                dpdv,
                 material: &sphere.material,
             }
         }
 
-        let oc = ray.origin() - self.center_at_time(ray.time());
+        stats::count_primitive_test();
+        let center = self.center_at_time(ray.time());
+        let oc = ray.origin() - center;
         let a = ray.direction().dot(&ray.direction());
         let b = oc.dot(&ray.direction());
         let c = oc.dot(&oc) - self.radius * self.radius;
@@ -81,11 +101,11 @@ impl<R: Rng> Hit<R> for Sphere {
         if discriminant > 0.0 {
             let mut t = (-b - discriminant.sqrt()) / a;
             if t > t_min && t < t_max {
-                return Some(calculate_hit_record(ray, t, self));
+                return Some(calculate_hit_record(ray, t, center, self));
             }
             t = (-b + discriminant.sqrt()) / a;
             if t > t_min && t < t_max {
-                return Some(calculate_hit_record(ray, t, self));
+                return Some(calculate_hit_record(ray, t, center, self));
             }
         }
         None
@@ -93,20 +113,31 @@ impl<R: Rng> Hit<R> for Sphere {
 
     fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
         let radius = Vec3::new(self.radius, self.radius, self.radius);
-        let center_start = self.center_at_time(time_start);
-        let aabb_0 = Aabb::new(center_start - radius, center_start + radius);
-        if self.motion_vector.is_some() {
-            let center_end = self.center_at_time(time_end);
-            let aabb_1 = Aabb::new(center_end - radius, center_end + radius);
-            Some(aabb_0.union(&aabb_1))
-        } else {
-            Some(aabb_0)
-        }
+        let centers = self.center.bounding_box(time_start, time_end);
+        Some(Aabb::new(centers.min - radius, centers.max + radius))
     }
 }
 
-fn sphere_uv(p: Vec3) -> (Float, Float) {
+pub(crate) fn sphere_uv(p: Vec3) -> (Float, Float) {
     let phi = p.z().atan2(p.x());
     let theta = p.y().asin();
     (1. - (phi + PI) / (2. * PI), (theta + FRAC_PI_2) / PI)
 }
+
+/// The partial derivatives of the position with respect to the coordinates of `sphere_uv` on a
+/// sphere with the given `radius` at the point with the unit `normal`. They vanish at the poles.
+pub(crate) fn sphere_derivatives(normal: Vec3, radius: Float) -> (Vec3, Vec3) {
+    let cos_theta = (normal.x() * normal.x() + normal.z() * normal.z()).sqrt();
+    if cos_theta == 0. {
+        return (Vec3::zero(), Vec3::zero());
+    }
+    let dpdu = 2. * PI * radius * Vec3::new(normal.z(), 0., -normal.x());
+    let dpdv = PI
+        * radius
+        * Vec3::new(
+            -normal.y() * normal.x() / cos_theta,
+            cos_theta,
+            -normal.y() * normal.z() / cos_theta,
+        );
+    (dpdu, dpdv)
+}
@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord};
+use crate::material::Material;
+use crate::math::float::consts::PI;
+use crate::math::float::Float;
+use crate::math::solve_quartic;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::shape::cylinder::{angle_u, disk_extent};
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A torus around `axis` whose tube of `minor_radius` follows a circle of `major_radius`.
+///
+/// `u` is the angle around the axis and `v` the angle around the tube (both normalized to
+/// `[0, 1]`).
+#[derive(Debug, Clone)]
+pub struct Torus {
+    center: Vec3,
+    major_radius: Float,
+    minor_radius: Float,
+    frame: Onb,
+    material: Arc<Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vec3,
+        axis: Vec3,
+        major_radius: Float,
+        minor_radius: Float,
+        material: Arc<Material>,
+    ) -> Torus {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            frame: Onb::from_w(&axis.unit_vector()),
+            material,
+        }
+    }
+}
+
+impl<R: Rng> Hit<R> for Torus {
+    // `f64::from` is a no-op with the `f64` feature.
+    #[cfg_attr(feature = "f64", allow(clippy::useless_conversion))]
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
+        // In a local frame with the axis along z the torus is
+        // (|p|^2 - R^2 - r^2)^2 = 4R^2 (r^2 - z^2). Inserting the ray gives a quartic in t which
+        // we solve in double precision.
+        let o = self.frame.to_local(&(ray.origin() - self.center));
+        let d = self.frame.to_local(&ray.direction());
+        let (ox, oy, oz) = (f64::from(o.x()), f64::from(o.y()), f64::from(o.z()));
+        let (dx, dy, dz) = (f64::from(d.x()), f64::from(d.y()), f64::from(d.z()));
+        let major_squared = f64::from(self.major_radius).powi(2);
+        let minor_squared = f64::from(self.minor_radius).powi(2);
+
+        let dd = dx * dx + dy * dy + dz * dz;
+        let od = ox * dx + oy * dy + oz * dz;
+        let e = ox * ox + oy * oy + oz * oz - major_squared - minor_squared;
+        let four_major_squared = 4. * major_squared;
+        let t = solve_quartic([
+            e * e - four_major_squared * (minor_squared - oz * oz),
+            4. * od * e + 2. * four_major_squared * oz * dz,
+            2. * dd * e + 4. * od * od + four_major_squared * dz * dz,
+            4. * dd * od,
+            dd * dd,
+        ])
+        .into_iter()
+        .map(|t| t as Float)
+        .filter(|&t| t > t_min && t < t_max)
+        .fold(None, |closest: Option<Float>, t| match closest {
+            Some(c) if c <= t => Some(c),
+            _ => Some(t),
+        })?;
+
+        // The normal points from the closest point on the center circle to the hit point.
+        let p = o + t * d;
+        let radial = Vec3::new(p.x(), p.y(), 0.);
+        let ring = if radial.length_squared() > 0. {
+            self.major_radius * radial.unit_vector()
+        } else {
+            Vec3::new(self.major_radius, 0., 0.)
+        };
+        let normal = (p - ring).unit_vector();
+        let tube_angle = p.z().atan2(radial.length() - self.major_radius);
+        let dpdu = 2. * PI * Vec3::new(-p.y(), p.x(), 0.);
+        let outward = ring / self.major_radius;
+        let dpdv = 2.
+            * PI
+            * self.minor_radius
+            * (normal.dot(&outward) * Vec3::new(0., 0., 1.) - normal.z() * outward);
+
+        Some(HitRecord {
+            t,
+            u: angle_u(&p),
+            v: (tube_angle + PI) / (2. * PI),
+            p: ray.point_at_parameter(t),
+            normal: self.frame.to_world(&normal),
+            dpdu: self.frame.to_world(&dpdu),
+            dpdv: self.frame.to_world(&dpdv),
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        let r = self.minor_radius;
+        let extent = disk_extent(&self.frame.w(), self.major_radius) + Vec3::new(r, r, r);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
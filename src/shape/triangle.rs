@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use crate::bvh::Aabb;
+use crate::hit::{Hit, HitRecord, SampleSurface};
+use crate::material::Material;
+use crate::math::float::Float;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::stats;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A triangle with the vertices `a`, `b` and `c` (counterclockwise around the normal).
+#[derive(Debug, Clone)]
+pub struct Triangle {
+    vertices: [Vec3; 3],
+    uvs: [(Float, Float); 3],
+    normal: Vec3,
+    vertex_normals: Option<[Vec3; 3]>,
+    material: Arc<Material>,
+}
+
+impl Triangle {
+    /// The vertices get the texture coordinates (0, 0), (1, 0) and (0, 1).
+    pub fn new(a: Vec3, b: Vec3, c: Vec3, material: Arc<Material>) -> Triangle {
+        Triangle {
+            vertices: [a, b, c],
+            uvs: [(0., 0.), (1., 0.), (0., 1.)],
+            normal: (b - a).cross(&(c - a)).unit_vector(),
+            vertex_normals: None,
+            material,
+        }
+    }
+
+    pub fn with_uvs(mut self, uvs: [(Float, Float); 3]) -> Triangle {
+        self.uvs = uvs;
+        self
+    }
+
+    /// Interpolates the given normals over the triangle (smooth shading) instead of using the
+    /// normal of the plane.
+    pub fn with_normals(mut self, normals: [Vec3; 3]) -> Triangle {
+        self.vertex_normals = Some(normals);
+        self
+    }
+
+    pub fn vertices(&self) -> &[Vec3; 3] {
+        &self.vertices
+    }
+}
+
+impl<R: Rng> Hit<R> for Triangle {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float, _: &mut R) -> Option<HitRecord<'_>> {
+        let (t, b1, b2) = intersect_triangle(ray, &self.vertices, t_min, t_max)?;
+        let b0 = 1. - b1 - b2;
+        let normal = match &self.vertex_normals {
+            Some([n0, n1, n2]) => (b0 * *n0 + b1 * *n1 + b2 * *n2).unit_vector(),
+            None => self.normal,
+        };
+        let (dpdu, dpdv) = triangle_derivatives(&self.vertices, &self.uvs, &self.normal);
+        Some(HitRecord {
+            t,
+            u: b0 * self.uvs[0].0 + b1 * self.uvs[1].0 + b2 * self.uvs[2].0,
+            v: b0 * self.uvs[0].1 + b1 * self.uvs[1].1 + b2 * self.uvs[2].1,
+            p: ray.point_at_parameter(t),
+            normal,
+            dpdu,
+            dpdv,
+            material: &self.material,
+        })
+    }
+
+    fn bounding_box(&self, _: Float, _: Float) -> Option<Aabb> {
+        let aabb = self
+            .vertices
+            .iter()
+            .fold(Aabb::empty(), |aabb, &p| aabb.union(&Aabb::new(p, p)));
+        // Pad the AABB so it doesn't have zero thickness for axis-aligned triangles.
+        let padding = Vec3::new(0.0001, 0.0001, 0.0001);
+        Some(Aabb::new(aabb.min - padding, aabb.max + padding))
+    }
+}
+
+impl SampleSurface for Triangle {
+    fn area(&self) -> Float {
+        let [a, b, c] = self.vertices;
+        (b - a).cross(&(c - a)).length() / 2.
+    }
+
+    fn sample_surface<R: Rng>(&self, rng: &mut R) -> (Vec3, Vec3) {
+        let [a, b, c] = self.vertices;
+        let sqrt_r1 = rng.gen::<Float>().sqrt();
+        let r2 = rng.gen::<Float>();
+        let p = (1. - sqrt_r1) * a + sqrt_r1 * (1. - r2) * b + sqrt_r1 * r2 * c;
+        (p, self.normal)
+    }
+}
+
+/// The partial derivatives of the position with respect to the texture coordinates on a
+/// triangle. Degenerate texture coordinates get an arbitrary tangent frame around `normal`.
+pub(crate) fn triangle_derivatives(
+    vertices: &[Vec3; 3],
+    uvs: &[(Float, Float); 3],
+    normal: &Vec3,
+) -> (Vec3, Vec3) {
+    let duv02 = (uvs[0].0 - uvs[2].0, uvs[0].1 - uvs[2].1);
+    let duv12 = (uvs[1].0 - uvs[2].0, uvs[1].1 - uvs[2].1);
+    let dp02 = vertices[0] - vertices[2];
+    let dp12 = vertices[1] - vertices[2];
+    let determinant = duv02.0 * duv12.1 - duv02.1 * duv12.0;
+    if determinant.abs() < 1e-8 {
+        let frame = Onb::from_w(normal);
+        return (frame.u(), frame.v());
+    }
+    let dpdu = (duv12.1 * dp02 - duv02.1 * dp12) / determinant;
+    let dpdv = (duv02.0 * dp12 - duv12.0 * dp02) / determinant;
+    (dpdu, dpdv)
+}
+
+/// Intersects a ray with a triangle using the Möller-Trumbore algorithm. Returns `t` and the
+/// barycentric coordinates of the second and third vertex.
+pub(crate) fn intersect_triangle(
+    ray: &Ray,
+    vertices: &[Vec3; 3],
+    t_min: Float,
+    t_max: Float,
+) -> Option<(Float, Float, Float)> {
+    stats::count_primitive_test();
+    let [a, b, c] = *vertices;
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction().cross(&edge2);
+    let determinant = edge1.dot(&p);
+    if determinant.abs() < 1e-10 {
+        return None;
+    }
+    let inv_determinant = 1. / determinant;
+    let s = ray.origin() - a;
+    let b1 = s.dot(&p) * inv_determinant;
+    if !(0. ..=1.).contains(&b1) {
+        return None;
+    }
+    let q = s.cross(&edge1);
+    let b2 = ray.direction().dot(&q) * inv_determinant;
+    if b2 < 0. || b1 + b2 > 1. {
+        return None;
+    }
+    let t = edge2.dot(&q) * inv_determinant;
+    if t < t_min || t > t_max {
+        return None;
+    }
+    Some((t, b1, b2))
+}
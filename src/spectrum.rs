@@ -0,0 +1,260 @@
+use lazy_static::lazy_static;
+
+use crate::math::clamp;
+use crate::math::float::Float;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// The range of visible wavelengths (in nanometers) which are sampled.
+pub const LAMBDA_MIN: Float = 380.;
+pub const LAMBDA_MAX: Float = 780.;
+
+/// The wavelength of the Fraunhofer d line at which indices of refraction are usually quoted.
+const LAMBDA_D: Float = 587.6;
+
+/// Second radiation constant `h * c / k` in nanometer kelvin.
+const C2: Float = 1.438_777e7;
+
+/// Wien's displacement constant in nanometer kelvin.
+const WIEN: Float = 2.897_772e6;
+
+/// The reflectance spectra of Smits, "An RGB-to-Spectrum Conversion for Reflectances" (1999) in
+/// ten bins from 380 nm to 720 nm.
+const SMITS_WHITE: [Float; 10] = [1., 1., 0.9999, 0.9993, 0.9992, 0.9998, 1., 1., 1., 1.];
+const SMITS_CYAN: [Float; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0., 0., 0.,
+];
+const SMITS_MAGENTA: [Float; 10] = [1., 1., 0.9685, 0.2229, 0., 0.0458, 0.8369, 1., 1., 0.9959];
+const SMITS_YELLOW: [Float; 10] = [
+    0.0001, 0., 0.1088, 0.6651, 1., 1., 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [Float; 10] = [
+    0.1012, 0.0515, 0., 0., 0., 0., 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [Float; 10] = [0., 0., 0.0273, 0.7937, 1., 0.9418, 0.1719, 0., 0., 0.0025];
+const SMITS_BLUE: [Float; 10] = [
+    1., 1., 0.8916, 0.3323, 0., 0., 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+lazy_static! {
+    /// Divides the sRGB values of the color matching functions so that their means over the
+    /// sampled range are 1, i.e. a constant spectrum of 1 becomes white.
+    static ref RGB_NORMALIZATION: Vec3 = integrate(|lambda| xyz_to_linear_srgb(&cie_xyz(lambda)));
+}
+
+/// The mean of `f` over the sampled range of wavelengths.
+fn integrate<F: Fn(Float) -> Vec3>(f: F) -> Vec3 {
+    let steps = 400;
+    let sum = (0..steps).fold(Vec3::zero(), |sum, i| {
+        sum + f(LAMBDA_MIN + (LAMBDA_MAX - LAMBDA_MIN) * (i as Float + 0.5) / steps as Float)
+    });
+    sum / steps as Float
+}
+
+/// A piecewise Gaussian with a different width on each side of its peak.
+fn gaussian(x: Float, mu: Float, sigma_left: Float, sigma_right: Float) -> Float {
+    let sigma = if x < mu { sigma_left } else { sigma_right };
+    let t = (x - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+/// The CIE 1931 2° color matching functions at `lambda` (in nanometers) using the multi-lobe
+/// fit of Wyman et al., "Simple Analytic Approximations to the CIE XYZ Color Matching Functions"
+/// (2013).
+pub fn cie_xyz(lambda: Float) -> Vec3 {
+    let x = 1.056 * gaussian(lambda, 599.8, 37.9, 31.0)
+        + 0.362 * gaussian(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * gaussian(lambda, 501.1, 20.4, 26.2);
+    let y =
+        0.821 * gaussian(lambda, 568.8, 46.9, 40.5) + 0.286 * gaussian(lambda, 530.9, 16.3, 31.1);
+    let z =
+        1.217 * gaussian(lambda, 437.0, 11.8, 36.0) + 0.681 * gaussian(lambda, 459.0, 26.0, 13.8);
+    Vec3::new(x, y, z)
+}
+
+/// Converts CIE XYZ to linear sRGB (D65 white point).
+pub fn xyz_to_linear_srgb(xyz: &Vec3) -> Vec3 {
+    let (x, y, z) = (xyz.x(), xyz.y(), xyz.z());
+    Vec3::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+/// The linear sRGB color of light of a single wavelength, scaled so that its mean over
+/// uniformly sampled wavelengths is white. Some wavelengths are outside of the sRGB gamut and
+/// have negative components.
+pub fn wavelength_to_rgb(lambda: Float) -> Vec3 {
+    xyz_to_linear_srgb(&cie_xyz(lambda)) / *RGB_NORMALIZATION
+}
+
+/// Converts the mean of `value * cie_xyz(lambda)` over uniformly sampled wavelengths to linear
+/// sRGB with the same white balance as `wavelength_to_rgb`.
+pub fn xyz_to_rgb(xyz: &Vec3) -> Vec3 {
+    xyz_to_linear_srgb(xyz) / *RGB_NORMALIZATION
+}
+
+/// The value at `lambda` of a smooth spectrum with the given (linear) RGB color following Smits.
+pub fn rgb_to_spectrum(rgb: &Vec3, lambda: Float) -> Float {
+    let bin = clamp(((lambda - LAMBDA_MIN) / 34.).floor(), 0., 9.) as usize;
+    let (r, g, b) = (rgb.r(), rgb.g(), rgb.b());
+    if r <= g && r <= b {
+        r * SMITS_WHITE[bin]
+            + if g <= b {
+                (g - r) * SMITS_CYAN[bin] + (b - g) * SMITS_BLUE[bin]
+            } else {
+                (b - r) * SMITS_CYAN[bin] + (g - b) * SMITS_GREEN[bin]
+            }
+    } else if g <= r && g <= b {
+        g * SMITS_WHITE[bin]
+            + if r <= b {
+                (r - g) * SMITS_MAGENTA[bin] + (b - r) * SMITS_BLUE[bin]
+            } else {
+                (b - g) * SMITS_MAGENTA[bin] + (r - b) * SMITS_RED[bin]
+            }
+    } else {
+        b * SMITS_WHITE[bin]
+            + if r <= g {
+                (r - b) * SMITS_YELLOW[bin] + (g - r) * SMITS_GREEN[bin]
+            } else {
+                (g - b) * SMITS_YELLOW[bin] + (r - g) * SMITS_RED[bin]
+            }
+    }
+}
+
+/// Colors (attenuations and emissions) are given in RGB. Along a path with a wavelength they're
+/// converted to the value of their spectrum at it which is stored in every channel.
+pub fn at_wavelength(rgb: &Vec3, wavelength: Option<Float>) -> Vec3 {
+    match wavelength {
+        Some(lambda) => {
+            let value = rgb_to_spectrum(rgb, lambda);
+            Vec3::new(value, value, value)
+        }
+        None => *rgb,
+    }
+}
+
+/// Samples a wavelength uniformly from the visible range.
+pub fn sample_wavelength<R: Rng>(rng: &mut R) -> Float {
+    LAMBDA_MIN + (LAMBDA_MAX - LAMBDA_MIN) * rng.gen::<Float>()
+}
+
+/// A (possibly wavelength dependent) index of refraction. Formulas take the wavelength in
+/// micrometers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ior {
+    Constant(Float),
+    /// Cauchy's equation `n = a + b / λ²`.
+    Cauchy {
+        a: Float,
+        b: Float,
+    },
+    /// The Sellmeier equation `n² = 1 + Σ b_i λ² / (λ² - c_i)`.
+    Sellmeier {
+        b: [Float; 3],
+        c: [Float; 3],
+    },
+}
+
+impl Ior {
+    /// The common borosilicate crown glass (Schott N-BK7).
+    pub fn bk7() -> Ior {
+        Ior::Sellmeier {
+            b: [1.039_612, 0.231_792_34, 1.010_469_5],
+            c: [0.006_000_698_7, 0.020_017_914, 103.560_65],
+        }
+    }
+
+    pub fn fused_silica() -> Ior {
+        Ior::Sellmeier {
+            b: [0.696_166_3, 0.407_942_6, 0.897_479_4],
+            c: [0.004_679_148, 0.013_512_063, 97.934],
+        }
+    }
+
+    pub fn diamond() -> Ior {
+        Ior::Sellmeier {
+            b: [0.3306, 4.3356, 0.],
+            c: [0.030_625, 0.011_236, 0.],
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Ior::Constant(_))
+    }
+
+    /// The index of refraction at `lambda` nanometers.
+    pub fn at(&self, lambda: Float) -> Float {
+        let micrometers = lambda / 1000.;
+        let l2 = micrometers * micrometers;
+        match self {
+            Ior::Constant(n) => *n,
+            Ior::Cauchy { a, b } => a + b / l2,
+            Ior::Sellmeier { b, c } => {
+                (1. + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<Float>()).sqrt()
+            }
+        }
+    }
+
+    /// The index of refraction at the d line (587.6 nm), used for paths without a wavelength.
+    pub fn nominal(&self) -> Float {
+        self.at(LAMBDA_D)
+    }
+}
+
+/// The spectral power distribution of an emitter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Spd {
+    /// Planck's law for a black body of the given temperature (in kelvin) normalized to a
+    /// maximum of 1.
+    Blackbody { temperature: Float },
+    /// `(wavelength, value)` samples sorted by wavelength which are linearly interpolated and
+    /// constant beyond the first and last one.
+    Tabulated(Vec<(Float, Float)>),
+}
+
+impl Spd {
+    pub fn blackbody(temperature: Float) -> Spd {
+        assert!(temperature > 0., "the temperature has to be positive");
+        Spd::Blackbody { temperature }
+    }
+
+    /// Panics if there are no samples.
+    pub fn tabulated(mut samples: Vec<(Float, Float)>) -> Spd {
+        assert!(!samples.is_empty(), "at least one sample is required");
+        samples.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        Spd::Tabulated(samples)
+    }
+
+    /// The value at `lambda` nanometers.
+    pub fn at(&self, lambda: Float) -> Float {
+        match self {
+            Spd::Blackbody { temperature } => {
+                // Dividing Planck's law by its value at the peak cancels the constants.
+                let planck = |lambda: Float| {
+                    1. / (lambda.powi(5) * ((C2 / (lambda * temperature)).exp() - 1.))
+                };
+                let peak = WIEN / temperature;
+                planck(lambda) / planck(peak)
+            }
+            Spd::Tabulated(samples) => {
+                let i = samples.iter().position(|s| s.0 > lambda);
+                match i {
+                    Some(0) => samples[0].1,
+                    Some(i) => {
+                        let (l0, v0) = samples[i - 1];
+                        let (l1, v1) = samples[i];
+                        v0 + (v1 - v0) * (lambda - l0) / (l1 - l0)
+                    }
+                    None => samples[samples.len() - 1].1,
+                }
+            }
+        }
+    }
+
+    /// The linear sRGB color of the distribution with the white balance of `wavelength_to_rgb`.
+    pub fn to_rgb(&self) -> Vec3 {
+        integrate(|lambda| self.at(lambda) * wavelength_to_rgb(lambda))
+    }
+}
@@ -0,0 +1,59 @@
+//! Per-thread counters for ray traversal work.
+//!
+//! `Bvh::hit` and the shape intersectors bump these counters so a renderer can find out how much
+//! work a single ray caused (see `heatmap`). The counters are thread-local so the render threads
+//! don't contend on them.
+//!
+//! Counting sits in the innermost traversal loop so it's only compiled in with the `stats`
+//! feature. Without it the counting functions are no-ops and `take` always returns zeros.
+
+#[cfg(feature = "stats")]
+use std::cell::Cell;
+
+/// Whether the counters are compiled in (the `stats` feature).
+pub const ENABLED: bool = cfg!(feature = "stats");
+
+#[cfg(feature = "stats")]
+thread_local! {
+    static AABB_TESTS: Cell<u64> = const { Cell::new(0) };
+    static PRIMITIVE_TESTS: Cell<u64> = const { Cell::new(0) };
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TraversalStats {
+    pub aabb_tests: u64,
+    pub primitive_tests: u64,
+}
+
+impl TraversalStats {
+    pub fn total(&self) -> u64 {
+        self.aabb_tests + self.primitive_tests
+    }
+}
+
+#[inline(always)]
+pub fn count_aabb_test() {
+    #[cfg(feature = "stats")]
+    AABB_TESTS.with(|c| c.set(c.get() + 1));
+}
+
+#[inline(always)]
+pub fn count_primitive_test() {
+    #[cfg(feature = "stats")]
+    PRIMITIVE_TESTS.with(|c| c.set(c.get() + 1));
+}
+
+/// Returns the counters of the current thread and resets them to zero.
+#[cfg(feature = "stats")]
+pub fn take() -> TraversalStats {
+    TraversalStats {
+        aabb_tests: AABB_TESTS.with(|c| c.replace(0)),
+        primitive_tests: PRIMITIVE_TESTS.with(|c| c.replace(0)),
+    }
+}
+
+/// Returns the counters of the current thread and resets them to zero.
+#[cfg(not(feature = "stats"))]
+pub fn take() -> TraversalStats {
+    TraversalStats::default()
+}
@@ -0,0 +1,169 @@
+use crate::hit::HitRecord;
+use crate::material::{random_in_unit_sphere, MaterialTrait};
+use crate::math::float::Float;
+use crate::microfacet::{fresnel_dielectric, reflect, refract};
+use crate::ray::Ray;
+use crate::spectrum;
+use crate::vec3::Vec3;
+use crate::Rng;
+
+/// A translucent material (skin, wax, marble, milk) where light enters through a smooth
+/// dielectric boundary and scatters isotropically inside until it leaves again (a volumetric
+/// random walk).
+///
+/// The walk happens one step per scattering: a ray inside which hits the boundary samples a
+/// free-flight distance along itself and either scatters at that point or reaches the boundary.
+/// These steps are walk steps (`MaterialTrait::is_walk_step`) so long walks through dense media
+/// aren't cut off by the path depth. The shape has to be closed and nothing else may be inside of
+/// it.
+///
+/// The single-scattering `albedo` and the `mean_free_path` are given per color channel. The
+/// distance is sampled with the coefficient of a random channel and weighted with the average
+/// density of all channels (hero channel sampling) so that chromatic media stay unbiased. Media
+/// whose mean free paths differ a lot between channels still converge slowly.
+#[derive(Debug, Clone)]
+pub struct Subsurface {
+    albedo: Vec3,
+    mean_free_path: Vec3,
+    ref_idx: Float,
+}
+
+impl Subsurface {
+    /// Panics if a component of `mean_free_path` isn't positive.
+    pub fn new(albedo: Vec3, mean_free_path: Vec3) -> Subsurface {
+        assert!(
+            mean_free_path.r() > 0. && mean_free_path.g() > 0. && mean_free_path.b() > 0.,
+            "the mean free path has to be positive"
+        );
+        Subsurface {
+            albedo,
+            mean_free_path,
+            ref_idx: 1.33,
+        }
+    }
+
+    /// From the (reduced) scattering and absorption coefficients per millimeter, e.g. measured
+    /// by Jensen et al., "A Practical Model for Subsurface Light Transport" (2001).
+    pub fn from_coefficients(
+        sigma_s: Vec3,
+        sigma_a: Vec3,
+        millimeters_per_unit: Float,
+    ) -> Subsurface {
+        let sigma_t = sigma_s + sigma_a;
+        Subsurface::new(
+            sigma_s / sigma_t,
+            Vec3::new(1., 1., 1.) / (millimeters_per_unit * sigma_t),
+        )
+    }
+
+    pub fn marble(millimeters_per_unit: Float) -> Subsurface {
+        Subsurface::from_coefficients(
+            Vec3::new(2.19, 2.62, 3.00),
+            Vec3::new(0.0021, 0.0041, 0.0071),
+            millimeters_per_unit,
+        )
+        .with_ior(1.5)
+    }
+
+    pub fn skin(millimeters_per_unit: Float) -> Subsurface {
+        Subsurface::from_coefficients(
+            Vec3::new(0.74, 0.88, 1.01),
+            Vec3::new(0.032, 0.17, 0.48),
+            millimeters_per_unit,
+        )
+        .with_ior(1.4)
+    }
+
+    pub fn whole_milk(millimeters_per_unit: Float) -> Subsurface {
+        Subsurface::from_coefficients(
+            Vec3::new(2.55, 3.21, 3.77),
+            Vec3::new(0.0011, 0.0024, 0.014),
+            millimeters_per_unit,
+        )
+    }
+
+    pub fn skim_milk(millimeters_per_unit: Float) -> Subsurface {
+        Subsurface::from_coefficients(
+            Vec3::new(0.70, 1.22, 1.90),
+            Vec3::new(0.0014, 0.0025, 0.0142),
+            millimeters_per_unit,
+        )
+    }
+
+    /// The index of refraction of the boundary (1.33 by default).
+    pub fn with_ior(mut self, ref_idx: Float) -> Subsurface {
+        self.ref_idx = ref_idx;
+        self
+    }
+
+    /// Reflects or refracts `direction` (unit length) at the boundary with the given `normal`
+    /// facing it. `eta` is the relative index of refraction of the other side.
+    fn cross_boundary<R: Rng>(direction: &Vec3, normal: &Vec3, eta: Float, rng: &mut R) -> Vec3 {
+        let wo = -*direction;
+        if rng.gen::<Float>() < fresnel_dielectric(wo.dot(normal), eta) {
+            return reflect(&wo, normal);
+        }
+        refract(&wo, normal, eta).unwrap_or_else(|| reflect(&wo, normal))
+    }
+}
+
+impl MaterialTrait for Subsurface {
+    fn scatter<R: Rng>(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord<'_>,
+        rng: &mut R,
+    ) -> Option<(Ray, Vec3)> {
+        let direction = ray.direction().unit_vector();
+        let white = Vec3::new(1., 1., 1.);
+        if direction.dot(&hit_record.normal) < 0. {
+            // Entering (or reflected off) the surface
+            let scattered =
+                Subsurface::cross_boundary(&direction, &hit_record.normal, self.ref_idx, rng);
+            return Some((ray.spawn(hit_record.p, scattered), white));
+        }
+
+        // Paths with a wavelength see the same medium in every channel.
+        let albedo = spectrum::at_wavelength(&self.albedo, ray.wavelength());
+        let mean_free_path = spectrum::at_wavelength(&self.mean_free_path, ray.wavelength());
+        let sigma_t = white / mean_free_path;
+        let distance = hit_record.t * ray.direction().length();
+
+        let channel = (rng.gen::<Float>() * 3.) as usize % 3;
+        let sampled = -(1. - rng.gen::<Float>()).ln() / sigma_t[channel];
+        if sampled < distance {
+            // Scatters inside: the density is the average of the densities of all channels.
+            let transmittance = exp(-sampled * sigma_t);
+            let pdf = mean(&(sigma_t * transmittance));
+            let origin = ray.origin() + sampled * direction;
+            let scattered = random_in_unit_sphere(rng);
+            return Some((
+                ray.spawn(origin, scattered),
+                albedo * sigma_t * transmittance / pdf,
+            ));
+        }
+
+        // Reaches the boundary with the average probability of all channels.
+        let transmittance = exp(-distance * sigma_t);
+        let probability = mean(&transmittance);
+        let scattered =
+            Subsurface::cross_boundary(&direction, &-hit_record.normal, 1. / self.ref_idx, rng);
+        Some((
+            ray.spawn(hit_record.p, scattered),
+            transmittance / probability,
+        ))
+    }
+
+    fn is_walk_step(&self, ray: &Ray, hit_record: &HitRecord<'_>) -> bool {
+        // Every hit from the inside scatters in the volume or leaves it
+        ray.direction().dot(&hit_record.normal) >= 0.
+    }
+}
+
+fn exp(v: Vec3) -> Vec3 {
+    Vec3::new(v.r().exp(), v.g().exp(), v.b().exp())
+}
+
+fn mean(v: &Vec3) -> Float {
+    (v.r() + v.g() + v.b()) / 3.
+}
@@ -20,6 +20,11 @@ impl Texture {
         Texture::Constant(Constant { color })
     }
 
+    /// A constant gray, for parameters which are a single number.
+    pub fn scalar(value: Float) -> Texture {
+        Texture::constant(Vec3::new(value, value, value))
+    }
+
     pub fn checker_board(texture0: Texture, texture1: Texture, square_size: Float) -> Texture {
         Texture::CheckerBoard(CheckerBoard {
             texture0: Box::new(texture0),